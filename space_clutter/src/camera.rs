@@ -0,0 +1,156 @@
+use nannou::prelude::*;
+use serde::Deserialize;
+use std::time::Duration;
+
+use crate::config;
+use crate::{ASTEROID_MAX_SIZE, TIMESTEP};
+
+const CAMERA_PATH: &str = "assets/camera.toml";
+const DEFAULT_CAMERA: &str = include_str!("../assets/camera.toml");
+
+const ASTEROID_TRAUMA: f32 = 0.4;
+const FRAGMENT_TRAUMA: f32 = 0.15;
+const CRACK_TRAUMA: f32 = 0.2;
+const CRASH_TRAUMA: f32 = 0.9;
+const BOMB_TRAUMA: f32 = 0.8;
+/* A smart bomb whites out the screen, fading over about a quarter of a second */
+const BOMB_FLASH: f32 = 0.7;
+const FLASH_DECAY: f32 = 3.0;
+
+/* Unrelated frequencies so the wobble doesn't visibly repeat */
+const SHAKE_FREQUENCY: (f32, f32, f32) = (37.0, 29.0, 23.0);
+
+#[derive(Deserialize,Clone)]
+pub struct CameraSettings{
+    max_offset: f32,
+    max_angle: f32,
+    trauma_decay: f32,
+    hit_stop_base_ms: f32,
+    hit_stop_per_size_ms: f32,
+}
+
+impl CameraSettings{
+    /* A hit stop can't run backwards, anything below nothing counts as nothing */
+    pub fn load() -> CameraSettings{
+        let mut settings: CameraSettings = config::load(CAMERA_PATH, DEFAULT_CAMERA);
+        for ms in [&mut settings.hit_stop_base_ms, &mut settings.hit_stop_per_size_ms]{
+            if !(*ms >= 0.0 && ms.is_finite()){
+                log::warn!("{} has a hit stop of {} ms, using 0", CAMERA_PATH, ms);
+                *ms = 0.0;
+            }
+        }
+        settings
+    }
+}
+
+pub struct Camera{
+    pub settings: CameraSettings,
+    /* Accessibility switches, set from the player's options */
+    pub shake: bool,
+    pub hit_stop: bool,
+    pub flash_enabled: bool,
+    trauma: f32,
+    time: f32,
+    offset: Vec2,
+    angle: f32,
+    freeze: Duration,
+    flash: f32,
+}
+
+impl Camera{
+    pub fn new(settings: CameraSettings) -> Camera{
+        Camera{
+            settings,
+            shake: true,
+            hit_stop: true,
+            flash_enabled: true,
+            trauma: 0.0,
+            time: 0.0,
+            offset: vec2(0.0, 0.0),
+            angle: 0.0,
+            freeze: Duration::ZERO,
+            flash: 0.0,
+        }
+    }
+
+    pub fn reset(&mut self){
+        self.trauma = 0.0;
+        self.offset = vec2(0.0, 0.0);
+        self.angle = 0.0;
+        self.freeze = Duration::ZERO;
+        self.flash = 0.0;
+    }
+
+    pub fn add_trauma(&mut self, amount: f32){
+        if self.shake{
+            self.trauma = (self.trauma + amount).min(1.0);
+        }
+    }
+
+    /* Stops the simulation for a moment so big hits land */
+    pub fn hit_stop(&mut self, size: f32){
+        if self.hit_stop{
+            let ms = self.settings.hit_stop_base_ms + self.settings.hit_stop_per_size_ms * size;
+            self.freeze = self.freeze.max(Duration::from_secs_f32(ms / 1000.0));
+        }
+    }
+
+    pub fn asteroid_destroyed(&mut self, size: f32, fragment: bool){
+        let scale = size / ASTEROID_MAX_SIZE;
+        if fragment{
+            self.add_trauma(FRAGMENT_TRAUMA * scale);
+        }
+        else{
+            self.add_trauma(ASTEROID_TRAUMA * scale);
+            self.hit_stop(size);
+        }
+    }
+
+    pub fn asteroid_cracked(&mut self, size: f32){
+        self.add_trauma(CRACK_TRAUMA * size / ASTEROID_MAX_SIZE);
+    }
+
+    pub fn ship_crashed(&mut self){
+        self.add_trauma(CRASH_TRAUMA);
+    }
+
+    pub fn smart_bomb(&mut self){
+        self.add_trauma(BOMB_TRAUMA);
+        self.hit_stop(ASTEROID_MAX_SIZE);
+        if self.flash_enabled{
+            self.flash = BOMB_FLASH;
+        }
+    }
+
+    /* How opaque the white flash over the screen is right now */
+    pub fn flash(&self) -> f32{
+        self.flash
+    }
+
+    pub fn frozen(&self) -> bool{
+        !self.freeze.is_zero()
+    }
+
+    /* Called once per step, returns whether the simulation should sit this step out */
+    pub fn update(&mut self) -> bool{
+        if self.frozen(){
+            self.freeze = self.freeze.saturating_sub(TIMESTEP);
+            return true;
+        }
+
+        let dt = TIMESTEP.as_secs_f32();
+        self.time += dt;
+        self.trauma = (self.trauma - self.settings.trauma_decay * dt).max(0.0);
+        self.flash = (self.flash - FLASH_DECAY * dt).max(0.0);
+
+        let shake = self.trauma * self.trauma;
+        self.offset = vec2((self.time * SHAKE_FREQUENCY.0).sin(), (self.time * SHAKE_FREQUENCY.1).sin()) * self.settings.max_offset * shake;
+        self.angle = deg_to_rad(self.settings.max_angle) * shake * (self.time * SHAKE_FREQUENCY.2).sin();
+        false
+    }
+
+    /* Everything drawn through the returned Draw gets the shake applied */
+    pub fn view(&self, draw: &Draw) -> Draw{
+        draw.xy(self.offset).rotate(self.angle)
+    }
+}
@@ -0,0 +1,48 @@
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+const APP_DIR: &str = "space_clutter";
+
+/* Reads a TOML config from disk, falling back to the copy built into the
+ * binary when the file is missing or doesn't parse. Each config keeps that
+ * copy as an `include_str!` of the same file in assets, as its `DEFAULT_*`. */
+pub fn load<T: DeserializeOwned>(path: &str, default: &str) -> T{
+    let from_file = std::fs::read_to_string(path)
+        .map_err(|e| e.to_string())
+        .and_then(|text| toml::from_str(&text).map_err(|e| e.to_string()));
+
+    match from_file{
+        Ok(config) => config,
+        Err(e) => {
+            log::warn!("Could not load {}: {}, using defaults", path, e);
+            toml::from_str(default).expect("Built-in config is invalid")
+        }
+    }
+}
+
+/* The platform's per-user data folder, or next to the game if there isn't one */
+pub fn data_dir() -> PathBuf{
+    let var = |name: &str| std::env::var_os(name).filter(|value| !value.is_empty()).map(PathBuf::from);
+    let base = if cfg!(target_os = "windows"){
+        var("APPDATA")
+    }
+    else if cfg!(target_os = "macos"){
+        var("HOME").map(|home| home.join("Library/Application Support"))
+    }
+    else{
+        var("XDG_DATA_HOME").or_else(|| var("HOME").map(|home| home.join(".local/share")))
+    };
+    base.map(|base| base.join(APP_DIR)).unwrap_or_default()
+}
+
+/* Writes a TOML file alongside then swaps it in, so a crash part way never leaves half a file */
+pub fn save<T: Serialize>(path: &Path, value: &T) -> std::io::Result<()>{
+    if let Some(dir) = path.parent(){
+        std::fs::create_dir_all(dir)?;
+    }
+    let text = toml::to_string(value).map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+    let temporary = path.with_extension("tmp");
+    std::fs::write(&temporary, text)?;
+    std::fs::rename(&temporary, path)
+}
@@ -0,0 +1,328 @@
+use gilrs::Button;
+use nannou::prelude::Key;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+use space_clutter::{config, StateEvents};
+
+use crate::menu::{Nav, Row, Widget};
+
+const CONTROLS_PATH: &str = "assets/controls.toml";
+const DEFAULT_CONTROLS: &str = include_str!("../assets/controls.toml");
+/* Rebinding from the options menu saves the player's own copy under this name */
+const CONTROLS_FILE: &str = "controls.toml";
+
+/* Anything else is kept for the menus, Escape above all so there's always a way back */
+const BINDABLE_KEYS: &[Key] = &[
+    Key::A, Key::B, Key::C, Key::D, Key::E, Key::F, Key::G, Key::H, Key::I, Key::J, Key::K, Key::L, Key::M,
+    Key::N, Key::O, Key::P, Key::Q, Key::R, Key::S, Key::T, Key::U, Key::V, Key::W, Key::X, Key::Y, Key::Z,
+    Key::Key0, Key::Key1, Key::Key2, Key::Key3, Key::Key4, Key::Key5, Key::Key6, Key::Key7, Key::Key8, Key::Key9,
+    Key::Numpad0, Key::Numpad1, Key::Numpad2, Key::Numpad3, Key::Numpad4,
+    Key::Numpad5, Key::Numpad6, Key::Numpad7, Key::Numpad8, Key::Numpad9,
+    Key::Left, Key::Right, Key::Up, Key::Down, Key::Space, Key::Return, Key::Tab,
+    Key::LShift, Key::RShift, Key::LControl, Key::RControl, Key::LAlt, Key::RAlt,
+    Key::Comma, Key::Period, Key::Slash, Key::Semicolon, Key::Apostrophe,
+    Key::LBracket, Key::RBracket, Key::Minus, Key::Equals, Key::Backslash,
+];
+
+/* Everything a ship can be told to do, whatever key or button it came from */
+#[derive(Copy,Clone,PartialEq)]
+pub enum Action{
+    Left,
+    Right,
+    Thrust,
+    Fire,
+    Switch,
+    Pause,
+}
+
+/* The ones each ship binds separately, pause is shared */
+pub const SHIP_ACTIONS: [Action; 5] = [Action::Left, Action::Right, Action::Thrust, Action::Fire, Action::Switch];
+
+impl Action{
+    pub fn name(self) -> &'static str{
+        match self{
+            Action::Left => "LEFT",
+            Action::Right => "RIGHT",
+            Action::Thrust => "THRUST",
+            Action::Fire => "FIRE",
+            Action::Switch => "WEAPON",
+            Action::Pause => "PAUSE",
+        }
+    }
+}
+
+/* What a key, button or stick did to the ship it belongs to */
+#[derive(Copy,Clone)]
+pub enum Command{
+    Pressed(Action),
+    Released(Action),
+    /* Past the deadzone, -1 is hard right and 1 hard left, 0 back to the keys */
+    Turn(f32),
+}
+
+impl Command{
+    /* The simulation's side of it. A stick is read straight into the controls instead. */
+    pub fn state(self) -> StateEvents{
+        match self{
+            Command::Pressed(Action::Left) => StateEvents::LeftKeyPress,
+            Command::Released(Action::Left) => StateEvents::LeftKeyRelease,
+            Command::Pressed(Action::Right) => StateEvents::RightKeyPress,
+            Command::Released(Action::Right) => StateEvents::RightKeyRelease,
+            Command::Pressed(Action::Thrust) => StateEvents::UpKeyPress,
+            Command::Released(Action::Thrust) => StateEvents::UpKeyRelease,
+            Command::Pressed(Action::Fire) => StateEvents::SpaceKeyPress,
+            Command::Released(Action::Fire) => StateEvents::SpaceKeyRelease,
+            Command::Pressed(Action::Switch) => StateEvents::SwitchKeyPress,
+            Command::Pressed(Action::Pause) => StateEvents::PauseKeyPress,
+            Command::Released(_) | Command::Turn(_) => StateEvents::NoneKeyPress,
+        }
+    }
+}
+
+/* Names of the keys or buttons for each of a ship's actions */
+#[derive(Serialize,Deserialize,Clone,Default)]
+#[serde(default)]
+pub struct Mapping{
+    pub left: Vec<String>,
+    pub right: Vec<String>,
+    pub thrust: Vec<String>,
+    pub fire: Vec<String>,
+    pub switch: Vec<String>,
+}
+
+impl Mapping{
+    fn get(&self, action: Action) -> &[String]{
+        match action{
+            Action::Left => &self.left,
+            Action::Right => &self.right,
+            Action::Thrust => &self.thrust,
+            Action::Fire => &self.fire,
+            Action::Switch => &self.switch,
+            Action::Pause => &[],
+        }
+    }
+
+    fn get_mut(&mut self, action: Action) -> Option<&mut Vec<String>>{
+        match action{
+            Action::Left => Some(&mut self.left),
+            Action::Right => Some(&mut self.right),
+            Action::Thrust => Some(&mut self.thrust),
+            Action::Fire => Some(&mut self.fire),
+            Action::Switch => Some(&mut self.switch),
+            Action::Pause => None,
+        }
+    }
+
+    fn action_of(&self, name: &str) -> Option<Action>{
+        SHIP_ACTIONS.into_iter().find(|&action| self.get(action).iter().any(|bound| bound == name))
+    }
+}
+
+#[derive(Serialize,Deserialize,Clone)]
+pub struct Bindings{
+    pub stick_deadzone: f32,
+    pub trigger_threshold: f32,
+    pub pause_keys: Vec<String>,
+    pub pause_buttons: Vec<String>,
+    #[serde(rename = "player")]
+    pub players: Vec<Mapping>,
+    pub pad: Mapping,
+}
+
+fn key_name(key: Key) -> String{
+    format!("{:?}", key)
+}
+
+fn button_name(button: Button) -> String{
+    format!("{:?}", button)
+}
+
+pub fn bindable(key: Key) -> bool{
+    BINDABLE_KEYS.contains(&key)
+}
+
+/* Letter keys type on the initials screen, so they're kept out of its navigation */
+pub fn letter(key: Key) -> bool{
+    let name = key_name(key);
+    name.len() == 1 && name.chars().all(|c| c.is_ascii_alphabetic())
+}
+
+fn path() -> PathBuf{
+    config::data_dir().join(CONTROLS_FILE)
+}
+
+impl Bindings{
+    /* The player's own copy if they've rebound anything, the shipped one otherwise */
+    pub fn load() -> Bindings{
+        let own = path();
+        let bindings: Bindings = if own.exists(){
+            config::load(&own.to_string_lossy(), DEFAULT_CONTROLS)
+        }
+        else{
+            config::load(CONTROLS_PATH, DEFAULT_CONTROLS)
+        };
+        bindings.clamped()
+    }
+
+    /* Thresholds out of range would leave the stick or triggers dead or stuck on */
+    fn clamped(mut self) -> Bindings{
+        self.stick_deadzone = self.stick_deadzone.clamp(0.0, 0.9);
+        self.trigger_threshold = self.trigger_threshold.clamp(0.0, 1.0);
+        self
+    }
+
+    pub fn save(&self){
+        let path = path();
+        match config::save(&path, self){
+            Ok(()) => log::info!("Saved controls {}", path.display()),
+            Err(error) => log::warn!("Could not save controls: {}", error),
+        }
+    }
+
+    /* Which ship a key belongs to and what it does there, pause goes to the first */
+    pub fn key(&self, key: Key) -> Option<(usize, Action)>{
+        let name = key_name(key);
+        if self.pause_keys.contains(&name){
+            return Some((0, Action::Pause));
+        }
+        self.players.iter().enumerate().find_map(|(player, mapping)| mapping.action_of(&name).map(|action| (player, action)))
+    }
+
+    /* Menus always answer to the arrows, Return, Space and Escape, and to
+     * whatever a ship steers, thrusts, fires and switches with. Whatever pauses
+     * the game also backs out of its menus. */
+    pub fn nav(&self, key: Key) -> Option<Nav>{
+        match key{
+            Key::Up => Some(Nav::Up),
+            Key::Down => Some(Nav::Down),
+            Key::Left => Some(Nav::Left),
+            Key::Right => Some(Nav::Right),
+            Key::Return | Key::Space => Some(Nav::Select),
+            Key::Escape => Some(Nav::Back),
+            _ => self.key(key).map(|(_, action)| match action{
+                Action::Left => Nav::Left,
+                Action::Right => Nav::Right,
+                Action::Thrust => Nav::Up,
+                Action::Switch => Nav::Down,
+                Action::Fire => Nav::Select,
+                Action::Pause => Nav::Back,
+            }),
+        }
+    }
+
+    /* Pads find their way round menus the usual way whatever they're bound to,
+     * with the D-pad, South to pick and East or the pause button to go back */
+    pub fn nav_button(&self, button: Button) -> Option<Nav>{
+        match button{
+            Button::DPadUp => Some(Nav::Up),
+            Button::DPadDown => Some(Nav::Down),
+            Button::DPadLeft => Some(Nav::Left),
+            Button::DPadRight => Some(Nav::Right),
+            Button::South => Some(Nav::Select),
+            Button::East => Some(Nav::Back),
+            _ if self.pause_buttons.contains(&button_name(button)) => Some(Nav::Back),
+            _ => None,
+        }
+    }
+
+    pub fn button(&self, button: Button) -> Option<Action>{
+        let name = button_name(button);
+        if self.pause_buttons.contains(&name){
+            return Some(Action::Pause);
+        }
+        self.pad.action_of(&name)
+    }
+
+    /* Stick position to a turn, scaled so it starts from nothing at the edge of the deadzone */
+    pub fn stick(&self, x: f32) -> f32{
+        let dead = self.stick_deadzone;
+        if x.abs() <= dead{
+            0.0
+        }
+        else{
+            -x.signum() * ((x.abs() - dead) / (1.0 - dead)).min(1.0)
+        }
+    }
+
+    /* The captured key becomes the action's first, taken off anything else it did.
+     * Any further keys from the file stay as they were. */
+    pub fn bind(&mut self, player: usize, action: Action, key: Key){
+        let name = key_name(key);
+        self.pause_keys.retain(|bound| *bound != name);
+        for mapping in &mut self.players{
+            for other in SHIP_ACTIONS{
+                if let Some(keys) = mapping.get_mut(other){
+                    keys.retain(|bound| *bound != name);
+                }
+            }
+        }
+
+        if let Some(keys) = self.players.get_mut(player).and_then(|mapping| mapping.get_mut(action)){
+            if keys.is_empty(){
+                keys.push(name);
+            }
+            else{
+                keys[0] = name;
+            }
+        }
+    }
+
+    pub fn row(&self, player: usize, action: Action) -> Row{
+        let keys = self.players.get(player).map(|mapping| mapping.get(action)).unwrap_or(&[]);
+        let keys = keys.iter().map(|key| key.to_uppercase()).collect::<Vec<String>>().join(" / ");
+        Row{ label: format!("P{} {}", player + 1, action.name()), widget: Widget::KeyCapture(keys) }
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    fn defaults() -> Bindings{
+        toml::from_str(DEFAULT_CONTROLS).unwrap()
+    }
+
+    fn is(found: Option<(usize, Action)>, player: usize, action: Action) -> bool{
+        found.is_some_and(|found| found == (player, action))
+    }
+
+    #[test]
+    fn shipped_bindings_map_keys_to_ships(){
+        let bindings = defaults();
+        assert!(is(bindings.key(Key::Up), 0, Action::Thrust));
+        assert!(is(bindings.key(Key::W), 1, Action::Thrust));
+        assert!(is(bindings.key(Key::Escape), 0, Action::Pause));
+        assert!(bindings.key(Key::Z).is_none());
+        assert!(bindings.button(Button::South) == Some(Action::Fire));
+        assert!(bindings.button(Button::Start) == Some(Action::Pause));
+    }
+
+    #[test]
+    fn rebinding_moves_the_key_and_survives_a_save(){
+        let mut bindings = defaults();
+        bindings.bind(0, Action::Fire, Key::W);
+        assert!(is(bindings.key(Key::W), 0, Action::Fire));
+        assert!(bindings.key(Key::Space).is_none(), "the old key is let go");
+        assert!(bindings.players[1].thrust.is_empty(), "taken off the other ship");
+        /* Menus follow the new binding too */
+        assert!(bindings.nav(Key::W) == Some(Nav::Select));
+
+        let saved: Bindings = toml::from_str(&toml::to_string(&bindings).unwrap()).unwrap();
+        assert!(is(saved.key(Key::W), 0, Action::Fire));
+        assert!(saved.key(Key::Space).is_none());
+        assert_eq!(saved.players[0].fire, bindings.players[0].fire);
+    }
+
+    #[test]
+    fn thresholds_are_kept_in_range(){
+        let bindings = Bindings{ stick_deadzone: 5.0, trigger_threshold: -1.0, ..defaults() }.clamped();
+        assert_eq!(bindings.stick_deadzone, 0.9);
+        assert_eq!(bindings.trigger_threshold, 0.0);
+
+        let bindings = defaults();
+        assert_eq!(bindings.stick(bindings.stick_deadzone / 2.0), 0.0);
+        assert_eq!(bindings.stick(1.0), -1.0);
+        assert_eq!(bindings.stick(-1.0), 1.0);
+    }
+}
@@ -0,0 +1,225 @@
+use nannou::prelude::*;
+use nannou::text::Font;
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use crate::spatial::View;
+use crate::menu;
+use space_clutter::net::Session;
+use space_clutter::powerup::PICKUP_RADIUS;
+use space_clutter::{asteroid_hitbox, missile_contact, ship_contact_points, World};
+use space_clutter::{ASTEROID_MAX_SIZE, SPACESHIP_HEIGHT, SPACESHIP_PEAK, SPACESHIP_WIDTH, TIMESTEP};
+
+/* FPS and frame time are averaged over this many frames */
+const FRAME_HISTORY: usize = 60;
+/* Time scale runs between these, a tenth at a time */
+const SCALE_STEP: f32 = 0.1;
+const MIN_SCALE: f32 = 0.1;
+const MAX_SCALE: f32 = 2.0;
+/* Velocity lines reach where each thing will be this many steps from now */
+const VELOCITY_STEPS: f32 = 15.0;
+const CONTACT_SIZE: f32 = 4.0;
+const LINE_HEIGHT: f32 = 16.0;
+
+/* The overlay and the clock controls that only work while it's showing */
+pub struct Debug{
+    pub on: bool,
+    pub frozen: bool,
+    pub step: bool,
+    pub scale: f32,
+    frames: VecDeque<Duration>,
+}
+
+impl Debug{
+    pub fn new() -> Debug{
+        Debug{
+            on: false,
+            frozen: false,
+            step: false,
+            scale: 1.0,
+            frames: VecDeque::with_capacity(FRAME_HISTORY),
+        }
+    }
+
+    /* Turning it off puts the clock back to normal too */
+    pub fn toggle(&mut self){
+        self.on ^= true;
+        if !self.on{
+            self.frozen = false;
+            self.step = false;
+            self.scale = 1.0;
+        }
+    }
+
+    pub fn slower(&mut self){
+        self.scale = ((self.scale / SCALE_STEP).round() - 1.0).max(MIN_SCALE / SCALE_STEP) * SCALE_STEP;
+    }
+
+    pub fn faster(&mut self){
+        self.scale = ((self.scale / SCALE_STEP).round() + 1.0).min(MAX_SCALE / SCALE_STEP) * SCALE_STEP;
+    }
+
+    /* Wall-clock time between frames, whatever the time scale is doing */
+    pub fn record(&mut self, since_last: Duration){
+        if self.frames.len() == FRAME_HISTORY{
+            self.frames.pop_front();
+        }
+        self.frames.push_back(since_last);
+    }
+
+    fn frame_times(&self) -> (f32, f32){
+        if self.frames.is_empty(){
+            return (0.0, 0.0);
+        }
+        let total: Duration = self.frames.iter().sum();
+        let mean = total.as_secs_f32() / self.frames.len() as f32;
+        let worst = self.frames.iter().max().copied().unwrap_or_default().as_secs_f32();
+        (mean, worst)
+    }
+}
+
+fn cross(draw: &Draw, position: Point2, color: Rgba){
+    draw.line()
+        .start(position - vec2(CONTACT_SIZE, 0.0))
+        .end(position + vec2(CONTACT_SIZE, 0.0))
+        .weight(1.0)
+        .color(color);
+    draw.line()
+        .start(position - vec2(0.0, CONTACT_SIZE))
+        .end(position + vec2(0.0, CONTACT_SIZE))
+        .weight(1.0)
+        .color(color);
+}
+
+fn velocity(draw: &Draw, position: Point2, per_step: Vec2){
+    draw.line()
+        .start(position)
+        .end(position + per_step * VELOCITY_STEPS)
+        .weight(1.0)
+        .color(rgba(0.3, 1.0, 0.3, 0.8));
+}
+
+/* The shapes the collision code really tests, drawn where the simulation has them
+ * rather than where the frame blended them to */
+pub fn draw_shapes(draw: &Draw, view: &View, world: &World){
+    let hitbox = rgba(1.0, 0.3, 0.3, 0.8);
+    let reach = rgba(0.3, 0.6, 1.0, 0.6);
+
+    /* New rocks can't land in a band through any ship or spawn point, or near the edge */
+    let exclusion = rgba(1.0, 1.0, 0.0, 0.08);
+    for player in &world.players{
+        for position in [player.position, player.spawn]{
+            let position = view.place(position);
+            draw.rect()
+                .x_y(position.x, 0.0)
+                .w_h(SPACESHIP_WIDTH * 2.0, view.screen.h())
+                .color(exclusion);
+            draw.rect()
+                .x_y(0.0, position.y)
+                .w_h(view.screen.w(), SPACESHIP_HEIGHT * 2.0)
+                .color(exclusion);
+        }
+    }
+    let inner = view.place(world.field.xy());
+    draw.rect()
+        .xy(inner)
+        .wh(world.field.wh() - vec2(ASTEROID_MAX_SIZE, ASTEROID_MAX_SIZE) * 2.0)
+        .no_fill()
+        .stroke(rgba(1.0, 1.0, 0.0, 0.5))
+        .stroke_weight(1.0);
+
+    for asteroid in &world.asteroid{
+        let position = view.place(asteroid.position);
+        draw.rect()
+            .xy(position)
+            .wh(asteroid_hitbox(asteroid).wh())
+            .no_fill()
+            .stroke(hitbox)
+            .stroke_weight(1.0);
+        velocity(draw, position, asteroid.velocity);
+    }
+
+    for player in &world.players{
+        if player.alive{
+            let offset = view.place(player.position) - player.position;
+            for point in ship_contact_points(player){
+                cross(draw, point + offset, hitbox);
+            }
+            velocity(draw, player.position + offset, player.velocity * TIMESTEP.as_secs_f32());
+            /* Other ships' shots hit inside this when friendly fire is on */
+            if world.friendly_fire{
+                draw.ellipse()
+                    .xy(player.position + offset)
+                    .radius(SPACESHIP_PEAK)
+                    .no_fill()
+                    .stroke(reach)
+                    .stroke_weight(1.0);
+            }
+        }
+
+        for missile in &player.missile{
+            let offset = view.place(missile.position) - missile.position;
+            cross(draw, missile_contact(missile) + offset, hitbox);
+            velocity(draw, missile.position + offset, missile.velocity);
+        }
+    }
+
+    for pickup in &world.pickups{
+        let position = view.place(pickup.position);
+        draw.ellipse()
+            .xy(position)
+            .radius(PICKUP_RADIUS + SPACESHIP_PEAK)
+            .no_fill()
+            .stroke(reach)
+            .stroke_weight(1.0);
+        velocity(draw, position, pickup.velocity);
+    }
+
+    for well in &world.wells{
+        draw.ellipse()
+            .xy(view.place(well.position))
+            .radius(world.hazards.horizon)
+            .no_fill()
+            .stroke(hitbox)
+            .stroke_weight(1.0);
+    }
+}
+
+/* Numbers down the top left, with the clock controls under them. The clock is
+ * left alone in a networked game, the link's numbers go there instead. */
+pub fn draw_stats(draw: &Draw, win: Rect, font: &Font, debug: &Debug, world: &World, particles: usize, net: Option<&Session>){
+    let clock = net.is_none();
+    let (mean, worst) = debug.frame_times();
+    let fps = if mean > 0.0 { 1.0 / mean } else { 0.0 };
+    let missiles: usize = world.players.iter().map(|player| player.missile.len()).sum();
+    let difficulty = &world.difficulty;
+
+    let mut lines = vec![
+        format!("FPS {:.0}  frame {:.1} ms  worst {:.1} ms", fps, mean * 1000.0, worst * 1000.0),
+        format!("tick {}  rocks {}  shots {}", world.frame, world.asteroid.len(), missiles),
+        format!("pickups {}  wells {}  particles {}", world.pickups.len(), world.wells.len(), particles),
+        format!("{}  {:.0} s  max rocks {}  speed {:.2}", difficulty.preset.name(), difficulty.elapsed().as_secs_f32(), difficulty.max_asteroids, difficulty.asteroid_speed),
+        format!("scale {:.2}  accuracy {:.0}%  deaths {}", difficulty.scale(), difficulty.accuracy() * 100.0, difficulty.deaths()),
+    ];
+    if let Some(session) = net{
+        lines.push(format!("rollbacks {}  deepest {}  stalls {}", session.rollbacks, session.deepest, session.stalls));
+        if let Some(player) = session.refused{
+            lines.push(format!("refused player {}, setups differ", player + 1));
+        }
+    }
+    if clock{
+        lines.push(if debug.frozen { "FROZEN  F5 run  F6 step".to_string() } else { "F5 freeze".to_string() });
+    }
+
+    let left = win.left() + 10.0;
+    let mut y = win.top() - 15.0;
+    for line in &lines{
+        menu::label(draw, font, line, left, y);
+        y -= LINE_HEIGHT;
+    }
+
+    if clock{
+        menu::label(draw, font, "F7 F8", left, y);
+        menu::draw_slider(draw, font, debug.scale / MAX_SCALE, &format!("x{:.1}", debug.scale), left + 50.0, y);
+    }
+}
@@ -0,0 +1,361 @@
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+use crate::config;
+
+const DIFFICULTY_PATH: &str = "assets/difficulty.toml";
+const DEFAULT_DIFFICULTY: &str = include_str!("../assets/difficulty.toml");
+
+#[derive(Copy,Clone,PartialEq)]
+pub enum Preset{
+    Easy,
+    Normal,
+    Arcade,
+}
+
+impl Preset{
+    pub fn name(self) -> &'static str{
+        match self{
+            Preset::Easy => "EASY",
+            Preset::Normal => "NORMAL",
+            Preset::Arcade => "ARCADE",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Preset>{
+        [Preset::Easy, Preset::Normal, Preset::Arcade].into_iter().find(|preset| preset.name() == name)
+    }
+
+    pub fn next(self) -> Preset{
+        match self{
+            Preset::Easy => Preset::Normal,
+            Preset::Normal => Preset::Arcade,
+            Preset::Arcade => Preset::Easy,
+        }
+    }
+
+    pub fn previous(self) -> Preset{
+        match self{
+            Preset::Easy => Preset::Arcade,
+            Preset::Normal => Preset::Easy,
+            Preset::Arcade => Preset::Normal,
+        }
+    }
+}
+
+#[derive(Deserialize,Serialize,Clone)]
+pub struct Curve{
+    base: f32,
+    min: f32,
+    cap: f32,
+    time: Vec<[f32; 2]>,
+    score: Vec<[f32; 2]>,
+}
+
+#[derive(Deserialize,Serialize,Clone)]
+pub struct Dynamic{
+    enabled: bool,
+    death_penalty: f32,
+    accuracy_target: f32,
+    accuracy_gain: f32,
+    min_shots: u32,
+    min_scale: f32,
+    max_scale: f32,
+}
+
+#[derive(Deserialize,Serialize,Clone)]
+pub struct PresetConfig{
+    interval: f32,
+    max_asteroids: Curve,
+    asteroid_speed: Curve,
+    dynamic: Dynamic,
+}
+
+#[derive(Deserialize,Serialize,Clone)]
+pub struct Presets{
+    easy: PresetConfig,
+    normal: PresetConfig,
+    arcade: PresetConfig,
+}
+
+impl Curve{
+    /* Ranges the wrong way round or points out of order would panic or jump about in play */
+    fn problem(&self) -> Option<String>{
+        let numbers = [self.base, self.min, self.cap].into_iter().chain(self.time.iter().chain(&self.score).flatten().copied());
+        if numbers.into_iter().any(|value| !value.is_finite()){
+            return Some("a number that isn't".to_string());
+        }
+        if self.min > self.cap{
+            return Some(format!("min {} over cap {}", self.min, self.cap));
+        }
+        for points in [&self.time, &self.score]{
+            if points.windows(2).any(|pair| pair[0][0] >= pair[1][0]){
+                return Some("points out of order".to_string());
+            }
+        }
+        None
+    }
+}
+
+impl PresetConfig{
+    fn problem(&self) -> Option<String>{
+        if !(self.interval > 0.0 && self.interval.is_finite()){
+            return Some(format!("interval {}", self.interval));
+        }
+        let (min_scale, max_scale) = (self.dynamic.min_scale, self.dynamic.max_scale);
+        if !min_scale.is_finite() || !max_scale.is_finite() || min_scale > max_scale{
+            return Some(format!("dynamic min_scale {} over max_scale {}", self.dynamic.min_scale, self.dynamic.max_scale));
+        }
+        self.max_asteroids.problem().map(|problem| format!("max_asteroids has {}", problem))
+            .or_else(|| self.asteroid_speed.problem().map(|problem| format!("asteroid_speed has {}", problem)))
+    }
+}
+
+impl Presets{
+    pub fn load() -> Presets{
+        checked(config::load(DIFFICULTY_PATH, DEFAULT_DIFFICULTY))
+    }
+
+    fn get(&self, preset: Preset) -> &PresetConfig{
+        match preset{
+            Preset::Easy => &self.easy,
+            Preset::Normal => &self.normal,
+            Preset::Arcade => &self.arcade,
+        }
+    }
+}
+
+/* One bad preset throws out the file, switching to it mid-session would otherwise panic */
+fn checked(presets: Presets) -> Presets{
+    for preset in [Preset::Easy, Preset::Normal, Preset::Arcade]{
+        if let Some(problem) = presets.get(preset).problem(){
+            log::warn!("{} preset {} has {}, using defaults", DIFFICULTY_PATH, preset.name(), problem);
+            return toml::from_str(DEFAULT_DIFFICULTY).expect("Built-in config is invalid");
+        }
+    }
+    presets
+}
+
+/* Piecewise linear through the points, flat beyond either end */
+fn sample(points: &[[f32; 2]], x: f32) -> f32{
+    match points{
+        [] => 0.0,
+        [first, ..] if x <= first[0] => first[1],
+        [.., last] if x >= last[0] => last[1],
+        _ => {
+            let i = points.iter().position(|p| p[0] > x).unwrap();
+            let (a, b) = (points[i - 1], points[i]);
+            a[1] + (b[1] - a[1]) * (x - a[0]) / (b[0] - a[0])
+        }
+    }
+}
+
+impl Curve{
+    fn value(&self, seconds: f32, score: u32, scale: f32) -> f32{
+        let added = sample(&self.time, seconds) + sample(&self.score, score as f32);
+        (self.base + added * scale).clamp(self.min, self.cap)
+    }
+}
+
+#[derive(Clone)]
+pub struct Difficulty{
+    pub max_asteroids: u32,
+    pub asteroid_speed: f32,
+    pub preset: Preset,
+    presets: Presets,
+    elapsed: Duration,
+    evaluated: Duration,
+    deaths: u32,
+    shots: u32,
+    hits: u32,
+    stress: Option<u32>,
+}
+
+impl Difficulty{
+    pub fn new(presets: Presets, preset: Preset, stress: Option<u32>) -> Difficulty{
+        let mut difficulty = Difficulty{
+            max_asteroids: 0,
+            asteroid_speed: 0.0,
+            preset,
+            presets,
+            elapsed: Duration::ZERO,
+            evaluated: Duration::ZERO,
+            deaths: 0,
+            shots: 0,
+            hits: 0,
+            stress,
+        };
+        difficulty.evaluate(0);
+        difficulty
+    }
+
+    /* Start of a new game, deaths carry over so the dynamic scaling can ease off */
+    pub fn reset(&mut self){
+        self.elapsed = Duration::ZERO;
+        self.evaluated = Duration::ZERO;
+        self.shots = 0;
+        self.hits = 0;
+        self.evaluate(0);
+    }
+
+    pub fn set_preset(&mut self, preset: Preset){
+        if preset != self.preset{
+            self.preset = preset;
+            self.deaths = 0;
+            self.reset();
+        }
+    }
+
+    pub fn record_shot(&mut self){
+        self.shots += 1;
+    }
+
+    pub fn record_hit(&mut self){
+        self.hits += 1;
+    }
+
+    pub fn record_death(&mut self){
+        self.deaths += 1;
+    }
+
+    /* Deaths carried into the next game, replays need them to scale the same way */
+    pub fn presets(&self) -> &Presets{
+        &self.presets
+    }
+
+    pub fn deaths(&self) -> u32{
+        self.deaths
+    }
+
+    pub fn set_deaths(&mut self, deaths: u32){
+        self.deaths = deaths;
+    }
+
+    /* Play time the curves are following */
+    pub fn elapsed(&self) -> Duration{
+        self.elapsed
+    }
+
+    pub fn accuracy(&self) -> f32{
+        if self.shots == 0{
+            0.0
+        }
+        else{
+            self.hits as f32 / self.shots as f32
+        }
+    }
+
+    /* How much of each curve's increment applies, 1.0 unless dynamic adjustment is on */
+    pub fn scale(&self) -> f32{
+        let dynamic = &self.presets.get(self.preset).dynamic;
+        if !dynamic.enabled{
+            return 1.0;
+        }
+
+        let mut scale = 1.0 - self.deaths as f32 * dynamic.death_penalty;
+        if self.shots >= dynamic.min_shots{
+            scale += (self.accuracy() - dynamic.accuracy_target) * dynamic.accuracy_gain;
+        }
+        scale.clamp(dynamic.min_scale, dynamic.max_scale)
+    }
+
+    pub fn tick(&mut self, dt: Duration, score: u32){
+        self.elapsed += dt;
+        let interval = Duration::from_secs_f32(self.presets.get(self.preset).interval);
+        if self.elapsed - self.evaluated >= interval{
+            self.evaluated = self.elapsed;
+            let previous = self.max_asteroids;
+            self.evaluate(score);
+            if self.max_asteroids > previous{
+                log::debug!("Difficulty Increase!");
+            }
+        }
+    }
+
+    fn evaluate(&mut self, score: u32){
+        let config = self.presets.get(self.preset);
+        let seconds = self.evaluated.as_secs_f32();
+        let scale = self.scale();
+
+        self.max_asteroids = config.max_asteroids.value(seconds, score, scale).round() as u32;
+        self.asteroid_speed = config.asteroid_speed.value(seconds, score, scale);
+
+        if let Some(stress) = self.stress{
+            self.max_asteroids = stress;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    fn defaults() -> Presets{
+        toml::from_str(DEFAULT_DIFFICULTY).unwrap()
+    }
+
+    fn curve(base: f32, min: f32, cap: f32, time: Vec<[f32; 2]>) -> Curve{
+        Curve{ base, min, cap, time, score: Vec::new() }
+    }
+
+    #[test]
+    fn curves_join_their_points_and_hold_past_the_ends(){
+        let points = [[0.0, 0.0], [10.0, 4.0], [20.0, 6.0]];
+        assert_eq!(sample(&points, -5.0), 0.0);
+        assert_eq!(sample(&points, 5.0), 2.0);
+        assert_eq!(sample(&points, 15.0), 5.0);
+        assert_eq!(sample(&points, 99.0), 6.0);
+        assert_eq!(sample(&[], 5.0), 0.0);
+    }
+
+    #[test]
+    fn values_stay_between_min_and_cap(){
+        let rising = curve(5.0, 2.0, 8.0, vec![[0.0, 0.0], [10.0, 100.0]]);
+        assert_eq!(rising.value(0.0, 0, 1.0), 5.0);
+        assert_eq!(rising.value(10.0, 0, 1.0), 8.0);
+        /* Scaled right down the increment can't take it under the floor either */
+        let falling = curve(5.0, 2.0, 8.0, vec![[0.0, 0.0], [10.0, -100.0]]);
+        assert_eq!(falling.value(10.0, 0, 1.0), 2.0);
+        assert_eq!(falling.value(10.0, 0, 0.0), 5.0);
+    }
+
+    #[test]
+    fn deaths_ease_the_scale_down_to_its_floor(){
+        let mut difficulty = Difficulty::new(defaults(), Preset::Easy, None);
+        let dynamic = difficulty.presets.get(Preset::Easy).dynamic.clone();
+        assert!(dynamic.enabled);
+        assert_eq!(difficulty.scale(), 1.0);
+
+        difficulty.record_death();
+        assert_eq!(difficulty.scale(), (1.0 - dynamic.death_penalty).clamp(dynamic.min_scale, dynamic.max_scale));
+
+        for _ in 0..100{
+            difficulty.record_death();
+        }
+        assert_eq!(difficulty.scale(), dynamic.min_scale);
+
+        /* A new preset is a fresh start */
+        difficulty.set_preset(Preset::Normal);
+        difficulty.set_preset(Preset::Easy);
+        assert_eq!(difficulty.scale(), 1.0);
+    }
+
+    #[test]
+    fn bad_presets_fall_back_to_the_defaults(){
+        let mut presets = defaults();
+        presets.normal.interval = 0.0;
+        assert_eq!(checked(presets).normal.interval, defaults().normal.interval);
+
+        let mut presets = defaults();
+        presets.arcade.max_asteroids.min = presets.arcade.max_asteroids.cap + 1.0;
+        assert_eq!(checked(presets).arcade.max_asteroids.min, defaults().arcade.max_asteroids.min);
+
+        let mut presets = defaults();
+        presets.easy.asteroid_speed.time.reverse();
+        assert!(checked(presets).easy.asteroid_speed.time.windows(2).all(|pair| pair[0][0] < pair[1][0]));
+
+        let mut presets = defaults();
+        presets.easy.interval = 7.0;
+        assert_eq!(checked(presets).easy.interval, 7.0);
+    }
+}
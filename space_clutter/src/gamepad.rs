@@ -0,0 +1,123 @@
+use gilrs::{Axis, Button, EventType, GamepadId, Gilrs};
+
+use crate::controls::{Bindings, Command};
+use crate::menu::Nav;
+
+/* The stick counts as a press in menus once it's pushed this far */
+const NAV_TILT: f32 = 0.5;
+
+/* Pads are handed to players in the order they are first used */
+pub struct Gamepads{
+    gilrs: Option<Gilrs>,
+    pads: Vec<GamepadId>,
+    /* Analogue buttons past the trigger threshold, so each press is only seen once */
+    held: Vec<(GamepadId, Button)>,
+    /* Which way each stick axis is pushed for the menus, -1, 0 or 1 */
+    tilted: Vec<(GamepadId, Axis, i8)>,
+}
+
+/* One pad event as both things it could mean. Play takes the command and the
+ * menus the nav, whichever is showing. */
+pub struct PadEvent{
+    pub player: usize,
+    pub command: Option<Command>,
+    pub nav: Option<Nav>,
+}
+
+/* Triggers report how far down they are, and are read against our own threshold */
+fn analogue(button: Button) -> bool{
+    matches!(button, Button::LeftTrigger2 | Button::RightTrigger2)
+}
+
+impl Gamepads{
+    pub fn new() -> Gamepads{
+        let gilrs = match Gilrs::new(){
+            Ok(gilrs) => Some(gilrs),
+            Err(e) => {
+                log::warn!("Gamepads unavailable: {}", e);
+                None
+            }
+        };
+
+        Gamepads{
+            gilrs,
+            pads: Vec::new(),
+            held: Vec::new(),
+            tilted: Vec::new(),
+        }
+    }
+
+    fn player(&mut self, id: GamepadId) -> usize{
+        match self.pads.iter().position(|&pad| pad == id){
+            Some(player) => player,
+            None => {
+                self.pads.push(id);
+                self.pads.len() - 1
+            }
+        }
+    }
+
+    /* A trigger crossing the threshold either way, None while it stays the same side */
+    fn trigger(&mut self, id: GamepadId, button: Button, value: f32, threshold: f32) -> Option<bool>{
+        let was = self.held.contains(&(id, button));
+        let now = value > threshold;
+        if now == was{
+            return None;
+        }
+        if now{
+            self.held.push((id, button));
+        }
+        else{
+            self.held.retain(|&held| held != (id, button));
+        }
+        Some(now)
+    }
+
+    /* The direction a stick axis has just been pushed in, None until it changes */
+    fn tilt(&mut self, id: GamepadId, axis: Axis, value: f32) -> Option<i8>{
+        let now = if value > NAV_TILT { 1 } else if value < -NAV_TILT { -1 } else { 0 };
+        let was = self.tilted.iter().find(|&&(pad, tilted, _)| pad == id && tilted == axis).map_or(0, |&(_, _, way)| way);
+        if now == was{
+            return None;
+        }
+        self.tilted.retain(|&(pad, tilted, _)| pad != id || tilted != axis);
+        self.tilted.push((id, axis, now));
+        Some(now).filter(|&way| way != 0)
+    }
+
+    /* Drains pending pad events */
+    pub fn poll(&mut self, bindings: &Bindings) -> Vec<PadEvent>{
+        let mut events = Vec::new();
+        while let Some(event) = self.gilrs.as_mut().and_then(|gilrs| gilrs.next_event()){
+            let nav = match event.event{
+                EventType::ButtonPressed(button, _) => bindings.nav_button(button),
+                EventType::AxisChanged(axis @ (Axis::LeftStickX | Axis::LeftStickY), value, _) => {
+                    match (axis, self.tilt(event.id, axis, value)){
+                        (Axis::LeftStickX, Some(way)) => Some(if way > 0 { Nav::Right } else { Nav::Left }),
+                        (_, Some(way)) => Some(if way > 0 { Nav::Up } else { Nav::Down }),
+                        _ => None,
+                    }
+                }
+                _ => None,
+            };
+            let command = match event.event{
+                EventType::ButtonPressed(button, _) if !analogue(button) => bindings.button(button).map(Command::Pressed),
+                EventType::ButtonReleased(button, _) if !analogue(button) => bindings.button(button).map(Command::Released),
+                EventType::ButtonChanged(button, value, _) if analogue(button) => {
+                    match (self.trigger(event.id, button, value, bindings.trigger_threshold), bindings.button(button)){
+                        (Some(true), Some(action)) => Some(Command::Pressed(action)),
+                        (Some(false), Some(action)) => Some(Command::Released(action)),
+                        _ => None,
+                    }
+                }
+                EventType::AxisChanged(Axis::LeftStickX, x, _) => Some(Command::Turn(bindings.stick(x))),
+                _ => None,
+            };
+            if command.is_some() || nav.is_some(){
+                let player = self.player(event.id);
+                events.push(PadEvent{ player, command, nav });
+            }
+        }
+        events
+    }
+}
@@ -0,0 +1,303 @@
+use nannou::prelude::*;
+use std::time::Instant;
+
+use crate::difficulty::{Difficulty, Preset, Presets};
+use crate::rng::Rng;
+use crate::ship::heading;
+use crate::spatial::wrapped_offset;
+use crate::wave::GameMode;
+use crate::{playfield, world_step, Effect, Input, StateEvents, World};
+use crate::{ASTEROID_MAX_SIZE, SPACESHIP_PEAK, TIMESTEP, WINDOW_SIZE};
+
+/* Each action holds these keys down for one step and leaves the rest up */
+pub const ACTIONS: [&[StateEvents]; 11] = [
+    &[StateEvents::NoneKeyPress],
+    &[StateEvents::LeftKeyPress],
+    &[StateEvents::RightKeyPress],
+    &[StateEvents::UpKeyPress],
+    &[StateEvents::SpaceKeyPress],
+    &[StateEvents::UpKeyPress, StateEvents::LeftKeyPress],
+    &[StateEvents::UpKeyPress, StateEvents::RightKeyPress],
+    &[StateEvents::SpaceKeyPress, StateEvents::LeftKeyPress],
+    &[StateEvents::SpaceKeyPress, StateEvents::RightKeyPress],
+    &[StateEvents::SpaceKeyPress, StateEvents::UpKeyPress],
+    &[StateEvents::SwitchKeyPress],
+];
+
+/* Vector observations see this many of the closest rocks, padded when there are fewer */
+pub const NEAREST_ASTEROIDS: usize = 8;
+const SHIP_FEATURES: usize = 3;
+const ASTEROID_FEATURES: usize = 4;
+pub const VECTOR_SIZE: usize = SHIP_FEATURES + NEAREST_ASTEROIDS * ASTEROID_FEATURES;
+
+/* Raster observations are the playfield at one cell per this many pixels, top row first */
+pub const RASTER_SCALE: u32 = 10;
+pub const RASTER_WIDTH: usize = (WINDOW_SIZE.0 / RASTER_SCALE) as usize;
+pub const RASTER_HEIGHT: usize = (WINDOW_SIZE.1 / RASTER_SCALE) as usize;
+const RASTER_SHIP: u8 = 85;
+const RASTER_SHOT: u8 = 170;
+const RASTER_ASTEROID: u8 = 255;
+/* Closing speeds are given in units of this many pixels a step */
+const CLOSING_SCALE: f32 = 8.0;
+
+const POINT_REWARD: f32 = 1.0;
+const CRASH_PENALTY: f32 = 10.0;
+/* Five minutes of play, an episode still going by then is cut off */
+const EPISODE_STEPS: u32 = 60 * 60 * 5;
+
+const BENCH_STEPS: u32 = 100_000;
+const BENCH_SEED: u64 = 1;
+
+#[derive(Copy,Clone,PartialEq)]
+pub enum Observe{
+    Vector,
+    Raster,
+}
+
+pub enum Observation{
+    Vector(Vec<f32>),
+    Raster(Vec<u8>),
+}
+
+pub struct Info{
+    pub score: u32,
+    pub frame: u32,
+    pub wave: u32,
+    /* Cut off by the step limit rather than ended by a crash */
+    pub truncated: bool,
+}
+
+pub struct Step{
+    pub observation: Observation,
+    pub reward: f32,
+    pub done: bool,
+    pub info: Info,
+}
+
+/* Solo game for training agents against, in the style of Gym. Nothing is drawn
+ * or played, so it runs as fast as the simulation can step. */
+pub struct Environment{
+    world: World,
+    observe: Observe,
+}
+
+/* Ship velocity along and across the nose, then distance, bearing off the nose,
+ * closing speed and size for each of the nearest rocks, all scaled to about -1..1 */
+fn observe_vector(world: &World) -> Vec<f32>{
+    let win = world.field;
+    let player = &world.players[0];
+    let forward = heading(player.rotation);
+    let side = vec2(forward.y, -forward.x);
+    let velocity = player.velocity / world.handling.max_speed;
+    let ship_velocity = player.velocity * TIMESTEP.as_secs_f32();
+    let reach = vec2(win.w(), win.h()).length() / 2.0;
+
+    let mut observation = Vec::with_capacity(VECTOR_SIZE);
+    observation.extend_from_slice(&[velocity.dot(forward), velocity.dot(side), player.alive as u8 as f32]);
+
+    let mut nearest: Vec<(Vec2, Vec2, f32)> = world.asteroid.iter()
+        .map(|asteroid| {
+            let offset = wrapped_offset(asteroid.position - player.position, win);
+            let velocity = asteroid.velocity - ship_velocity;
+            (offset, velocity, asteroid.size)
+        })
+        .collect();
+    nearest.sort_by(|a, b| a.0.length_squared().total_cmp(&b.0.length_squared()));
+
+    for i in 0..NEAREST_ASTEROIDS{
+        match nearest.get(i){
+            Some(&(offset, velocity, size)) => {
+                let bearing = offset.dot(side).atan2(offset.dot(forward)) / PI;
+                let closing = -offset.normalize_or_zero().dot(velocity) / CLOSING_SCALE;
+                observation.extend_from_slice(&[offset.length() / reach, bearing, closing, size / ASTEROID_MAX_SIZE]);
+            }
+            None => observation.extend_from_slice(&[1.0, 0.0, 0.0, 0.0]),
+        }
+    }
+    observation
+}
+
+/* Fills every cell whose centre is within `radius` of `position`, through the wrap */
+fn plot(raster: &mut [u8], position: Point2, radius: f32, value: u8){
+    let win = playfield();
+    let scale = RASTER_SCALE as f32;
+    let radius = radius.max(scale / 2.0);
+    let reach = (radius / scale).ceil() as i32;
+    let col = ((position.x - win.left()) / scale).floor() as i32;
+    let row = ((win.top() - position.y) / scale).floor() as i32;
+
+    for y in row - reach..=row + reach{
+        for x in col - reach..=col + reach{
+            let centre = pt2(win.left() + (x as f32 + 0.5) * scale, win.top() - (y as f32 + 0.5) * scale);
+            if wrapped_offset(centre - position, win).length() > radius{
+                continue;
+            }
+            let index = y.rem_euclid(RASTER_HEIGHT as i32) as usize * RASTER_WIDTH + x.rem_euclid(RASTER_WIDTH as i32) as usize;
+            raster[index] = raster[index].max(value);
+        }
+    }
+}
+
+/* One byte per cell, brighter for whatever matters more when things overlap */
+fn observe_raster(world: &World) -> Vec<u8>{
+    let mut raster = vec![0; RASTER_WIDTH * RASTER_HEIGHT];
+    for player in &world.players{
+        if player.alive{
+            plot(&mut raster, player.position, SPACESHIP_PEAK, RASTER_SHIP);
+        }
+        for missile in &player.missile{
+            plot(&mut raster, missile.position, 0.0, RASTER_SHOT);
+        }
+    }
+    for asteroid in &world.asteroid{
+        plot(&mut raster, asteroid.position, asteroid.size / 2.0, RASTER_ASTEROID);
+    }
+    for well in &world.wells{
+        plot(&mut raster, well.position, world.hazards.horizon, RASTER_ASTEROID);
+    }
+    raster
+}
+
+impl Environment{
+    pub fn new(preset: Preset, mode: GameMode, observe: Observe) -> Environment{
+        let mut world = World::new(Difficulty::new(Presets::load(), preset, None), 0);
+        world.mode = mode;
+        Environment{ world, observe }
+    }
+
+    pub fn action_count(&self) -> usize{
+        ACTIONS.len()
+    }
+
+    pub fn world(&self) -> &World{
+        &self.world
+    }
+
+    /* Fresh episode, the same seed always plays out the same way for the same actions */
+    pub fn reset(&mut self, seed: u64) -> Observation{
+        self.world.difficulty.set_deaths(0);
+        self.world.restart(1, seed);
+        self.observation()
+    }
+
+    /* Panics on an action outside `action_count`, like a bad index would */
+    pub fn step(&mut self, action: usize) -> Step{
+        let mut input = Input::default();
+        for &event in ACTIONS[action]{
+            input.apply(event);
+        }
+
+        let score = self.world.players[0].score;
+        world_step(&mut self.world, &[input]);
+
+        /* Nobody is watching, so nothing the step wanted shown is kept */
+        let crashed = self.world.effects.iter().any(|effect| matches!(effect, Effect::Crashed(_)));
+        self.world.effects.clear();
+
+        let player = &self.world.players[0];
+        let mut reward = (player.score - score) as f32 * POINT_REWARD;
+        if crashed{
+            reward -= CRASH_PENALTY;
+        }
+
+        let terminated = self.world.all_crashed();
+        let truncated = !terminated && self.world.frame >= EPISODE_STEPS;
+        Step{
+            observation: self.observation(),
+            reward,
+            done: terminated || truncated,
+            info: Info{
+                score: player.score,
+                frame: self.world.frame,
+                wave: self.world.wave.number,
+                truncated,
+            },
+        }
+    }
+
+    pub fn observation(&self) -> Observation{
+        match self.observe{
+            Observe::Vector => Observation::Vector(observe_vector(&self.world)),
+            Observe::Raster => Observation::Raster(observe_raster(&self.world)),
+        }
+    }
+}
+
+/* Headless check of how much faster than real time random play steps, `--gym-bench` */
+pub fn run_benchmark(){
+    let mut rng = Rng::new(BENCH_SEED);
+
+    println!("{:>8} {:>10} {:>10} {:>12} {:>12}", "observe", "steps", "episodes", "steps/s", "x realtime");
+    for (name, observe) in [("vector", Observe::Vector), ("raster", Observe::Raster)]{
+        let mut environment = Environment::new(Preset::Normal, GameMode::Endless, observe);
+        let mut episodes = 1;
+        environment.reset(BENCH_SEED);
+
+        let start = Instant::now();
+        for _ in 0..BENCH_STEPS{
+            let action = rng.below(environment.action_count() as u32) as usize;
+            if environment.step(action).done{
+                episodes += 1;
+                environment.reset(rng.next_u64());
+            }
+        }
+
+        let rate = BENCH_STEPS as f64 / start.elapsed().as_secs_f64();
+        println!("{:>8} {:>10} {:>10} {:>12.0} {:>12.0}", name, BENCH_STEPS, episodes, rate, rate * TIMESTEP.as_secs_f64());
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    const TEST_STEPS: u32 = 2000;
+
+    fn vector(observation: Observation) -> Vec<f32>{
+        match observation{
+            Observation::Vector(vector) => vector,
+            Observation::Raster(_) => panic!("asked for a vector"),
+        }
+    }
+
+    /* Rewards and observations for one seeded run of random actions */
+    fn play(seed: u64) -> (Vec<f32>, Vec<Vec<f32>>){
+        let mut environment = Environment::new(Preset::Normal, GameMode::Endless, Observe::Vector);
+        let mut rng = Rng::new(seed);
+        environment.reset(seed);
+        let mut rewards = Vec::new();
+        let mut observations = Vec::new();
+        for _ in 0..TEST_STEPS{
+            let step = environment.step(rng.below(environment.action_count() as u32) as usize);
+            rewards.push(step.reward);
+            observations.push(vector(step.observation));
+            if step.done{
+                environment.reset(seed);
+            }
+        }
+        (rewards, observations)
+    }
+
+    #[test]
+    fn observations_are_the_advertised_size(){
+        let mut environment = Environment::new(Preset::Normal, GameMode::Waves, Observe::Raster);
+        match environment.reset(7){
+            Observation::Raster(raster) => assert_eq!(raster.len(), RASTER_WIDTH * RASTER_HEIGHT),
+            Observation::Vector(_) => panic!("asked for a raster"),
+        }
+
+        let mut environment = Environment::new(Preset::Normal, GameMode::Waves, Observe::Vector);
+        let observation = vector(environment.reset(7));
+        assert_eq!(observation.len(), VECTOR_SIZE);
+        assert!(observation.iter().all(|value| value.is_finite()));
+    }
+
+    #[test]
+    fn same_seed_same_episode(){
+        let (rewards, observations) = play(3);
+        let (again, observed_again) = play(3);
+        assert_eq!(rewards, again);
+        assert_eq!(observations, observed_again);
+        assert!(rewards.iter().any(|&reward| reward != 0.0), "nothing scored or crashed");
+    }
+}
@@ -0,0 +1,176 @@
+use nannou::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::config;
+use crate::spatial::wrapped_offset;
+use crate::{crash_player, Asteroid, Effect, World, TIMESTEP};
+
+const HAZARDS_PATH: &str = "assets/hazards.toml";
+const DEFAULT_HAZARDS: &str = include_str!("../assets/hazards.toml");
+/* Gives up on a well rather than searching forever for a clear spot */
+const PLACE_ATTEMPTS: u32 = 100;
+
+#[derive(Deserialize,Serialize,Clone)]
+pub struct Hazards{
+    pub enabled: bool,
+    pub first_wave: u32,
+    pub waves_per_well: u32,
+    pub max_wells: u32,
+    pub strength: f32,
+    pub max_pull: f32,
+    pub horizon: f32,
+    pub clearance: f32,
+}
+
+impl Hazards{
+    pub fn load() -> Hazards{
+        config::load(HAZARDS_PATH, DEFAULT_HAZARDS)
+    }
+
+    fn wells_for(&self, wave: u32) -> u32{
+        if !self.enabled || wave < self.first_wave{
+            return 0;
+        }
+        ((wave - self.first_wave) / self.waves_per_well.max(1) + 1).min(self.max_wells)
+    }
+}
+
+/* A black hole sitting still in the field, pulling everything towards it */
+#[derive(Clone)]
+pub struct Well{
+    pub position: Point2,
+}
+
+/* Fresh wells for the start of `wave`, clear of the ships so nobody spawns into one */
+pub fn place_wells(world: &mut World, wave: u32){
+    let win = world.field;
+    world.wells.clear();
+
+    for _ in 0..world.hazards.wells_for(wave){
+        for _ in 0..PLACE_ATTEMPTS{
+            let position = pt2(world.rng.range(win.left(), win.right()), world.rng.range(win.bottom(), win.top()));
+            let clear = |other: Point2| wrapped_offset(other - position, win).length() > world.hazards.clearance;
+            let safe = world.players.iter().all(|player| clear(player.position) && clear(player.spawn))
+                && world.wells.iter().all(|well| clear(well.position));
+            if safe{
+                world.wells.push(Well{ position });
+                break;
+            }
+        }
+    }
+}
+
+/* Inverse-square pull towards every well on something at `position`, in pixels per step per step */
+fn pull(wells: &[Well], hazards: &Hazards, win: Rect, position: Point2) -> Vec2{
+    wells.iter()
+        .map(|well| {
+            let offset = wrapped_offset(well.position - position, win);
+            let distance = offset.length().max(1.0);
+            offset / distance * (hazards.strength / (distance * distance)).min(hazards.max_pull)
+        })
+        .fold(Vec2::ZERO, |total, pull| total + pull)
+}
+
+fn swallowed(wells: &[Well], hazards: &Hazards, win: Rect, position: Point2) -> bool{
+    wells.iter().any(|well| wrapped_offset(well.position - position, win).length() < hazards.horizon)
+}
+
+/* Pulls ships, shots and rocks in, then takes whatever crossed a horizon. Shields
+ * and respawn cover don't help, nothing comes back out. */
+pub fn well_update(world: &mut World){
+    if world.wells.is_empty(){
+        return;
+    }
+
+    let dt = TIMESTEP.as_secs_f32();
+    for player in world.players.iter_mut().filter(|player| player.alive){
+        /* Ships keep their velocity per second */
+        player.velocity += pull(&world.wells, &world.hazards, world.field, player.position) / dt;
+    }
+    for missile in world.players.iter_mut().flat_map(|player| player.missile.iter_mut()){
+        missile.velocity += pull(&world.wells, &world.hazards, world.field, missile.position);
+    }
+    for asteroid in &mut world.asteroid{
+        asteroid.velocity += pull(&world.wells, &world.hazards, world.field, asteroid.position);
+    }
+
+    for i in 0..world.players.len(){
+        let player = &world.players[i];
+        if player.alive && swallowed(&world.wells, &world.hazards, world.field, player.position){
+            log::debug!(target: crate::logging::COLLISION, "Swallowed!");
+            crash_player(world, i);
+        }
+    }
+    for player in &mut world.players{
+        player.missile.retain(|missile| !swallowed(&world.wells, &world.hazards, world.field, missile.position));
+    }
+    let (gone, kept): (Vec<Asteroid>, Vec<Asteroid>) = std::mem::take(&mut world.asteroid)
+        .into_iter()
+        .partition(|asteroid| swallowed(&world.wells, &world.hazards, world.field, asteroid.position));
+    world.asteroid = kept;
+    for asteroid in gone{
+        world.effects.push(Effect::Cracked(asteroid));
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use crate::difficulty::{Difficulty, Preset, Presets};
+    use crate::playfield;
+
+    const HAZARDS: Hazards = Hazards{
+        enabled: true,
+        first_wave: 2,
+        waves_per_well: 2,
+        max_wells: 3,
+        strength: 1000.0,
+        max_pull: 0.5,
+        horizon: 12.0,
+        clearance: 150.0,
+    };
+
+    fn well(x: f32, y: f32) -> Vec<Well>{
+        vec![Well{ position: pt2(x, y) }]
+    }
+
+    #[test]
+    fn wells_pull_inverse_square_up_to_the_cap(){
+        let win = playfield();
+        let wells = well(0.0, 0.0);
+        let near = pull(&wells, &HAZARDS, win, pt2(100.0, 0.0));
+        let far = pull(&wells, &HAZARDS, win, pt2(200.0, 0.0));
+        assert!(near.x < 0.0 && near.y == 0.0, "pulls towards the well");
+        assert!((near.x / far.x - 4.0).abs() < 1e-3, "a quarter as hard at twice the distance");
+        assert_eq!(pull(&wells, &HAZARDS, win, pt2(5.0, 0.0)).length(), HAZARDS.max_pull);
+
+        /* Round the seam is the short way */
+        let across = pull(&well(win.right() - 10.0, 0.0), &HAZARDS, win, pt2(win.left() + 10.0, 0.0));
+        assert!(across.x < 0.0);
+
+        assert!(swallowed(&wells, &HAZARDS, win, pt2(HAZARDS.horizon - 1.0, 0.0)));
+        assert!(!swallowed(&wells, &HAZARDS, win, pt2(HAZARDS.horizon + 1.0, 0.0)));
+    }
+
+    #[test]
+    fn wells_come_with_the_waves_up_to_the_most(){
+        let counts: Vec<u32> = (0..10).map(|wave| HAZARDS.wells_for(wave)).collect();
+        assert_eq!(counts, [0, 0, 1, 1, 2, 2, 3, 3, 3, 3]);
+        assert_eq!(Hazards{ enabled: false, ..HAZARDS }.wells_for(9), 0);
+    }
+
+    #[test]
+    fn wells_are_placed_clear_of_ships_and_each_other(){
+        let mut world = World::new(Difficulty::new(Presets::load(), Preset::Normal, None), 7);
+        world.restart(2, 7);
+        world.hazards = HAZARDS;
+        place_wells(&mut world, 9);
+
+        assert_eq!(world.wells.len(), 3);
+        let clear = |a: Point2, b: Point2| wrapped_offset(a - b, world.field).length() > HAZARDS.clearance;
+        for (i, well) in world.wells.iter().enumerate(){
+            assert!(world.players.iter().all(|player| clear(well.position, player.position) && clear(well.position, player.spawn)));
+            assert!(world.wells[i + 1..].iter().all(|other| clear(well.position, other.position)));
+        }
+    }
+}
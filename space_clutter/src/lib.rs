@@ -0,0 +1,877 @@
+use nannou::prelude::*;
+use std::time::Duration;
+
+pub mod config;
+pub mod difficulty;
+pub mod gym;
+pub mod hazard;
+pub mod logging;
+pub mod net;
+pub mod pilot;
+pub mod powerup;
+pub mod replay;
+pub mod rng;
+pub mod rocks;
+pub mod scores;
+pub mod ship;
+pub mod spatial;
+pub mod stress;
+pub mod wave;
+pub mod weapon;
+
+use difficulty::Difficulty;
+use hazard::{Hazards, Well};
+use powerup::{Pickup, Power, Powers};
+use rng::Rng;
+use rocks::RockPhysics;
+use ship::Handling;
+use spatial::{wrap_point, wrapped_offset, SpatialHash, View};
+use wave::{GameMode, Wave};
+use weapon::{Arsenal, Behaviour};
+
+pub const WINDOW_SIZE: (u32, u32) = (640, 480);
+/* The large world is this many windows across each way, four screens' worth */
+pub const LARGE_WORLD_SCALE: f32 = 2.0;
+
+/* The simulation always steps at 60Hz, per-step constants below assume this */
+pub const TIMESTEP: Duration = Duration::from_nanos(1_000_000_000 / 60);
+pub const SPACESHIP_PEAK: f32 = 16.25;
+const SPACESHIP_TROUGH: f32 = 6.5;
+pub const SPACESHIP_WIDTH: f32 = 30.0;
+pub const SPACESHIP_HEIGHT: f32 = 39.0;
+/* Solo keeps the original one-hit game, co-op players get spare ships */
+const SOLO_LIVES: u32 = 1;
+const COOP_LIVES: u32 = 3;
+const RESPAWN_DELAY: Duration = Duration::from_secs(2);
+const RESPAWN_INVULNERABLE: Duration = Duration::from_secs(2);
+const COOP_SPAWN_OFFSET: f32 = 80.0;
+/* Networked games fill these spawn points in order */
+pub const MAX_PLAYERS: usize = 4;
+const SPAWN_POINTS: [(f32, f32); MAX_PLAYERS] = [
+    (-COOP_SPAWN_OFFSET, 0.0),
+    (COOP_SPAWN_OFFSET, 0.0),
+    (0.0, COOP_SPAWN_OFFSET),
+    (0.0, -COOP_SPAWN_OFFSET),
+];
+
+pub const ASTEROID_MAX_SIZE: f32 = 80.0;
+const ASTEROID_MIN_SIZE: f32 = 40.0;
+const ASTEROID_MAX_SPEED: f32 = 4.0;
+const ASTEROID_MIN_SPEED: f32 = -4.0;
+const ASTEROID_THICKNESS: f32 = 5.0;
+const ASTEROID_SPEED: f32 = 0.5;
+/* Rocks take a hit point of damage for every this much of their size on top of the first,
+ * so the biggest need two blaster shots and fragments go with one */
+const ASTEROID_SIZE_PER_HIT_POINT: f32 = 60.0;
+
+/* Broadphase grid cell size, half the largest asteroid keeps cells sparse */
+const BROADPHASE_CELL_SIZE: f32 = ASTEROID_MAX_SIZE / 2.0;
+/* Asteroid outlines are jittered up to 20% past their nominal radius */
+const ASTEROID_BOUND_SCALE: f32 = 0.6;
+
+#[derive(Copy,Clone)]
+pub enum StateEvents{
+    NoneKeyPress,
+    LeftKeyPress,
+    LeftKeyRelease,
+    RightKeyPress,
+    RightKeyRelease,
+    UpKeyPress,
+    UpKeyRelease,
+    SpaceKeyPress,
+    SpaceKeyRelease,
+    SwitchKeyPress,
+    PauseKeyPress,
+}
+
+
+#[derive(Clone)]
+pub struct Player{
+    pub position: Point2,
+    pub rotation: f32,
+    pub previous_position: Point2,
+    pub previous_rotation: f32,
+    pub rotation_inc: f32,
+    pub score: u32,
+    pub thrust: bool,
+    pub velocity: Vec2,
+    pub missile: Vec<Projectile>,
+    pub spawn: Point2,
+    pub lives: u32,
+    pub alive: bool,
+    pub respawn: Duration,
+    pub invulnerable: Duration,
+    pub powers: Powers,
+    /* Index into the arsenal, and how long until it can fire again */
+    pub weapon: usize,
+    pub reload: Duration,
+    /* Fire pressed while reloading, goes off as soon as the reload is done */
+    pub fire_queued: bool,
+}
+
+#[derive(Clone)]
+pub struct Asteroid{
+    pub position: Point2,
+    pub rotation: f32,
+    pub previous_position: Point2,
+    pub previous_rotation: f32,
+    pub rotation_speed: f32,
+    pub size: f32,
+    pub num_points: u32,
+    pub fragment: bool, // Whether it is a fragment or not
+    pub thickness: f32,
+    /* Pixels per step */
+    pub velocity: Vec2,
+    pub points: Vec<Point2>,
+    pub hp: u32,
+}
+
+#[derive(Clone)]
+pub struct Projectile{
+    pub hit: bool,
+    pub position: Point2,
+    pub rotation: f32,
+    pub previous_position: Point2,
+    pub weapon: usize,
+    pub behaviour: Behaviour,
+    /* Pixels per step, the nose follows it round */
+    pub velocity: Vec2,
+    pub size: f32,
+    pub damage: u32,
+    pub life: Duration,
+}
+
+/* What one ship's controls are doing for a single step. Fire and switch are
+ * latched by the key press and cleared once a step has used them, trigger is
+ * the fire button held. Turn is an analogue stick, positive to the left, and
+ * left and right only count while it's centred. */
+#[derive(Copy,Clone,Default,PartialEq)]
+pub struct Input{
+    pub left: bool,
+    pub right: bool,
+    pub thrust: bool,
+    pub fire: bool,
+    pub trigger: bool,
+    pub switch: bool,
+    pub turn: i8,
+}
+
+/* Bytes per ship per step in packets and replay files */
+pub(crate) const INPUT_BYTES: usize = 2;
+
+impl Input{
+    /* Buttons as bits, then the stick */
+    fn to_bytes(self) -> [u8; INPUT_BYTES]{
+        let bits = self.left as u8 | (self.right as u8) << 1 | (self.thrust as u8) << 2 | (self.fire as u8) << 3 | (self.trigger as u8) << 4 | (self.switch as u8) << 5;
+        [bits, self.turn as u8]
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Input{
+        let bits = bytes[0];
+        Input{
+            left: bits & 1 != 0,
+            right: bits & 2 != 0,
+            thrust: bits & 4 != 0,
+            fire: bits & 8 != 0,
+            trigger: bits & 16 != 0,
+            switch: bits & 32 != 0,
+            turn: bytes[1] as i8,
+        }
+    }
+
+    /* A key or button going down or up changes what the controls are holding */
+    pub fn apply(&mut self, event: StateEvents){
+        match event{
+            StateEvents::LeftKeyPress => {self.left = true},
+            StateEvents::LeftKeyRelease => {self.left = false},
+            StateEvents::RightKeyPress => {self.right = true},
+            StateEvents::RightKeyRelease => {self.right = false},
+            StateEvents::UpKeyPress => {self.thrust = true},
+            StateEvents::UpKeyRelease => {self.thrust = false},
+            StateEvents::SpaceKeyPress => {self.fire = true; self.trigger = true},
+            StateEvents::SpaceKeyRelease => {self.trigger = false},
+            StateEvents::SwitchKeyPress => {self.switch = true},
+            _ => { /* Do nowt */}
+        }
+    }
+}
+
+/* Things the simulation wants seen or heard, played back once the step is done */
+#[derive(Clone)]
+pub enum Effect{
+    Fired(usize),
+    Spark(Point2),
+    Destroyed(Asteroid),
+    Cracked(Asteroid),
+    /* Every rock a smart bomb cleared, as one blast rather than a crack each */
+    Bombed(Vec<Asteroid>),
+    Crashed(usize),
+    Collected(Power, Point2),
+}
+
+/* Everything that decides how play unfolds and nothing about how it looks or
+ * sounds. Stepping it with the same inputs from the same seed always lands on
+ * the same state, which the netcode relies on to roll back and replay. */
+#[derive(Clone)]
+pub struct World{
+    pub players: Vec<Player>,
+    pub asteroid: Vec<Asteroid>,
+    pub difficulty: Difficulty,
+    pub broadphase: SpatialHash,
+    pub mode: GameMode,
+    pub wave: Wave,
+    pub handling: Handling,
+    pub arsenal: Arsenal,
+    pub rocks: RockPhysics,
+    pub hazards: Hazards,
+    pub friendly_fire: bool,
+    /* Wrapping bounds in world space, the window only shows part of a large world */
+    pub large: bool,
+    pub field: Rect,
+    pub seed: u64,
+    pub rng: Rng,
+    pub frame: u32,
+    pub effects: Vec<Effect>,
+    pub pickups: Vec<Pickup>,
+    /* Left on the smart bomb's pause before rocks drift back in */
+    pub calm: Duration,
+    pub wells: Vec<Well>,
+}
+
+fn new_player(spawn: Point2, lives: u32) -> Player{
+    Player {
+        position: spawn,
+        rotation: 0.0,
+        previous_position: spawn,
+        previous_rotation: 0.0,
+        rotation_inc: 0.0,
+        score: 0,
+        thrust: false,
+        velocity: vec2(0.0, 0.0),
+        missile: Vec::new(),
+        spawn,
+        lives,
+        alive: true,
+        respawn: Duration::ZERO,
+        invulnerable: Duration::ZERO,
+        powers: Powers::default(),
+        weapon: 0,
+        reload: Duration::ZERO,
+        fire_queued: false,
+    }
+}
+
+/* Solo starts in the middle, more ships take the spawn points in turn */
+fn spawn_players(count: usize) -> Vec<Player>{
+    if count == 1{
+        vec![new_player(pt2(0.0, 0.0), SOLO_LIVES)]
+    }
+    else{
+        SPAWN_POINTS.iter()
+            .take(count)
+            .map(|&(x, y)| new_player(pt2(x, y), COOP_LIVES))
+            .collect()
+    }
+}
+
+/* The simulation runs in window coordinates whether or not there is a window */
+pub fn playfield() -> Rect{
+    Rect::from_w_h(WINDOW_SIZE.0 as f32, WINDOW_SIZE.1 as f32)
+}
+
+impl World{
+    pub fn new(difficulty: Difficulty, seed: u64) -> World{
+        World{
+            players: spawn_players(1),
+            asteroid: Vec::new(),
+            difficulty,
+            broadphase: SpatialHash::new(playfield(), BROADPHASE_CELL_SIZE),
+            mode: GameMode::Endless,
+            wave: Wave::new(),
+            handling: Handling::load(),
+            arsenal: Arsenal::load(),
+            rocks: RockPhysics::load(),
+            hazards: Hazards::load(),
+            friendly_fire: false,
+            large: false,
+            field: playfield(),
+            seed,
+            rng: Rng::new(seed),
+            frame: 0,
+            effects: Vec::new(),
+            pickups: Vec::new(),
+            calm: Duration::ZERO,
+            wells: Vec::new(),
+        }
+    }
+
+    /* New game with the settings picked on the menu */
+    pub fn restart(&mut self, players: usize, seed: u64){
+        self.players = spawn_players(players);
+        self.asteroid.clear();
+        self.difficulty.reset();
+        self.wave = Wave::new();
+        self.seed = seed;
+        self.rng = Rng::new(seed);
+        self.frame = 0;
+        self.effects.clear();
+        self.pickups.clear();
+        self.calm = Duration::ZERO;
+        self.wells.clear();
+    }
+
+    /* Takes effect straight away, set it before a restart */
+    pub fn set_large(&mut self, large: bool){
+        self.large = large;
+        self.field = if large { Rect::from_w_h(WINDOW_SIZE.0 as f32 * LARGE_WORLD_SCALE, WINDOW_SIZE.1 as f32 * LARGE_WORLD_SCALE) } else { playfield() };
+        self.broadphase = SpatialHash::new(self.field, BROADPHASE_CELL_SIZE);
+    }
+
+    /* Rock counts are tuned for one screen, a bigger field holds proportionally more */
+    fn crowd(&self, count: u32) -> usize{
+        let screens = (self.field.w() * self.field.h()) / (WINDOW_SIZE.0 * WINDOW_SIZE.1) as f32;
+        (count as f32 * screens).round() as usize
+    }
+
+    pub fn all_crashed(&self) -> bool{
+        self.players.iter().all(|player| !player.alive && player.lives == 0)
+    }
+}
+
+/* One shot from the ship's weapon, or a fan of three while the spread shot lasts */
+fn fire_missile(world: &mut World, player: usize)
+{
+    log::trace!("Firing missile");
+    let ship = &mut world.players[player];
+    let weapon = &world.arsenal.weapons[ship.weapon];
+    for &angle in ship.powers.spread(){
+        ship.missile.push(weapon::shot(ship.weapon, weapon, ship.position, ship.rotation + angle));
+        world.difficulty.record_shot();
+    }
+    ship.reload = ship.powers.cooldown(Duration::from_secs_f32(weapon.cooldown));
+    world.effects.push(Effect::Fired(ship.weapon));
+}
+
+fn has_missile_hit_edge(missile: &Projectile, win: Rect) -> bool{
+    let mut has_hit = false;
+
+    if missile.position.x > win.right()
+        || missile.position.x < win.left()
+        || missile.position.y > win.top()
+        || missile.position.y < win.bottom()
+    {
+        has_hit = true;
+    }
+
+    if missile.hit
+    {
+        has_hit = true;
+    }
+    
+    if has_hit
+    {
+        log::trace!("Removing missile from vector");
+    }
+
+    has_hit
+}
+
+/* The one point of a missile that's tested against the rocks */
+pub fn missile_contact(missile: &Projectile) -> Point2{
+    pt2(missile.position.x + (missile.size/2.0), missile.position.y + (missile.size/2.0))
+}
+
+/* Rocks are hit as an upright square their size across, whatever their outline */
+pub fn asteroid_hitbox(asteroid: &Asteroid) -> Rect{
+    Rect::from_xy_wh(asteroid.position, vec2(asteroid.size, asteroid.size))
+}
+
+/* Where a rock is as seen from `from`, its copy over the seam if that's the nearer one */
+fn nearest_image(asteroid: &Asteroid, from: Point2, field: Rect) -> Point2{
+    from + wrapped_offset(asteroid.position - from, field)
+}
+
+fn has_missile_hit_asteroid(missile: &Projectile, asteroid: &Asteroid, field: Rect) -> bool{
+    let contact = missile_contact(missile);
+    let position = nearest_image(asteroid, contact, field);
+    let left_edge:bool = contact.x > (position.x - (asteroid.size / 2.0));
+    let right_edge:bool = contact.x < (position.x + (asteroid.size / 2.0));
+    let top_edge:bool = contact.y < (position.y + (asteroid.size / 2.0));
+    let bottom_edge:bool = contact.y > (position.y - (asteroid.size / 2.0));
+
+    left_edge && right_edge && top_edge && bottom_edge
+}
+
+fn destroy_asteroid(rng: &mut Rng, asteroid: &Asteroid, score: &mut u32, fragment: &mut Vec<Asteroid>, pickups: &mut Vec<Pickup>){
+    log::debug!(target: logging::COLLISION, "Hit!");
+    *score += 1;
+
+    split_asteroid(rng, asteroid, fragment);
+    powerup::roll_drop(rng, asteroid, pickups);
+}
+
+/* Big rocks break into two fragments, fragments just vanish */
+fn split_asteroid(rng: &mut Rng, asteroid: &Asteroid, fragment: &mut Vec<Asteroid>){
+    if !asteroid.fragment{
+        let new_point_l = pt2(asteroid.position.x - (asteroid.size / 2.0), asteroid.position.y);
+        let asteroid_l = generate_asteroid(rng, new_point_l, 12, ASTEROID_MIN_SIZE / 2.0, ASTEROID_MAX_SIZE / 2.0, ASTEROID_SPEED, true);
+
+        let new_point_r = pt2(asteroid.position.x + (asteroid.size / 2.0), asteroid.position.y);
+        let asteroid_r = generate_asteroid(rng, new_point_r, 12, ASTEROID_MIN_SIZE / 2.0, ASTEROID_MAX_SIZE / 2.0, ASTEROID_SPEED, true);
+
+        fragment.push(asteroid_l);
+        fragment.push(asteroid_r);
+    }
+}
+
+/* Rebuilds the broadphase from the current asteroid positions */
+fn build_broadphase(grid: &mut SpatialHash, asteroids: &[Asteroid]){
+    grid.clear();
+    for (i, asteroid) in asteroids.iter().enumerate(){
+        grid.insert(i, asteroid.position, asteroid.size * ASTEROID_BOUND_SCALE);
+    }
+}
+
+/* Damage dealt to each asteroid by the missiles touching it, only testing
+ * asteroids sharing a grid cell. Piercing shots carry on through. */
+fn missile_hits(missiles: &mut [Projectile], asteroids: &[Asteroid], grid: &SpatialHash, field: Rect) -> Vec<u32>{
+    let mut damage = vec![0; asteroids.len()];
+    let mut candidates = Vec::new();
+
+    for missile in missiles{
+        grid.query(missile.position, missile.size / 2.0, &mut candidates);
+        for &i in &candidates{
+            if has_missile_hit_asteroid(missile, &asteroids[i], field){
+                missile.hit = missile.behaviour != Behaviour::Pierce;
+                damage[i] += missile.damage;
+            }
+        }
+    }
+
+    damage
+}
+
+/* Marks missiles that hit another player's ship and returns who got hit */
+fn friendly_fire_hits(players: &mut [Player]) -> Vec<usize>{
+    let mut hit_players = Vec::new();
+    for shooter in 0..players.len(){
+        for target in (0..players.len()).filter(|&target| target != shooter){
+            if !players[target].alive || !players[target].invulnerable.is_zero(){
+                continue;
+            }
+
+            let position = players[target].position;
+            for missile in &mut players[shooter].missile{
+                if !missile.hit && missile.position.distance(position) < SPACESHIP_PEAK{
+                    missile.hit = true;
+                    if !hit_players.contains(&target){
+                        hit_players.push(target);
+                    }
+                }
+            }
+        }
+    }
+    hit_players
+}
+
+/* Outline of the ship in its own frame, nose pointing up */
+pub fn ship_hull() -> [Point2; 4]{
+    [
+        pt2(-(SPACESHIP_WIDTH / 2.0), -(SPACESHIP_PEAK + SPACESHIP_TROUGH)),
+        pt2(0.0, -SPACESHIP_PEAK),
+        pt2(SPACESHIP_WIDTH / 2.0, -(SPACESHIP_PEAK + SPACESHIP_TROUGH)),
+        pt2(0.0, SPACESHIP_PEAK),
+    ]
+}
+
+/* Nose, left tail and right tail, the only parts of the ship that can touch a rock */
+pub fn ship_contact_points(player: &Player) -> [Point2; 3]{
+    let true_rotation = player.rotation + deg_to_rad(90.0);
+    let true_x = player.position.x + (SPACESHIP_PEAK * true_rotation.cos());
+    let true_y = player.position.y + (SPACESHIP_PEAK * true_rotation.sin());
+    
+    let r_tail_x = player.position.x + ((SPACESHIP_WIDTH / 2.0) * true_rotation.cos());
+    let r_tail_y = player.position.y + (-(SPACESHIP_PEAK + SPACESHIP_TROUGH) * true_rotation.sin());
+    
+    let l_tail_x = player.position.x - ((SPACESHIP_WIDTH / 2.0) * true_rotation.cos());
+    let l_tail_y = player.position.y + (-(SPACESHIP_PEAK + SPACESHIP_TROUGH) * true_rotation.sin());
+
+    [pt2(true_x, true_y), pt2(l_tail_x, l_tail_y), pt2(r_tail_x, r_tail_y)]
+}
+
+fn has_ship_hit_asteroid(player: &Player, asteroids: &[Asteroid], grid: &SpatialHash, field: Rect) -> bool{
+    let mut has_hit = false;
+
+    let [nose, l_tail, r_tail] = ship_contact_points(player);
+    let (true_x, true_y) = (nose.x, nose.y);
+    let (l_tail_x, l_tail_y) = (l_tail.x, l_tail.y);
+    let (r_tail_x, r_tail_y) = (r_tail.x, r_tail.y);
+
+    let mut candidates = Vec::new();
+    grid.query(player.position, SPACESHIP_HEIGHT, &mut candidates);
+
+    for asteroid in candidates.iter().map(|&i| &asteroids[i]){
+        let position = nearest_image(asteroid, player.position, field);
+        /* Has peak hit asteroid? */
+        let left_edge:bool = (true_x) > (position.x - (asteroid.size / 2.0));
+        let right_edge:bool = (true_x) < (position.x + (asteroid.size / 2.0));
+        let top_edge:bool = (true_y) < (position.y + (asteroid.size / 2.0));
+        let bottom_edge:bool = (true_y) > (position.y - (asteroid.size / 2.0));
+        
+        let l_left_edge:bool = (l_tail_x) > (position.x - (asteroid.size / 2.0));
+        let l_right_edge:bool = (l_tail_x) < (position.x + (asteroid.size / 2.0));
+        let l_top_edge:bool = (l_tail_y) < (position.y + (asteroid.size / 2.0));
+        let l_bottom_edge:bool = (l_tail_y) > (position.y - (asteroid.size / 2.0));
+        
+        let r_left_edge:bool = (r_tail_x) > (position.x - (asteroid.size / 2.0));
+        let r_right_edge:bool = (r_tail_x) < (position.x + (asteroid.size / 2.0));
+        let r_top_edge:bool = (r_tail_y) < (position.y + (asteroid.size / 2.0));
+        let r_bottom_edge:bool = (r_tail_y) > (position.y - (asteroid.size / 2.0));
+
+        if left_edge && right_edge && top_edge && bottom_edge
+        {
+            log::debug!(target: logging::COLLISION, "CRASH!");
+            has_hit = true;
+        }
+        else if l_left_edge && l_right_edge && l_top_edge && l_bottom_edge
+        {
+            log::debug!(target: logging::COLLISION, "LEFT CRASH!");
+            has_hit = true;
+        }
+        else if r_left_edge && r_right_edge && r_top_edge && r_bottom_edge
+        {
+            log::debug!(target: logging::COLLISION, "RIGHT CRASH!");
+            has_hit = true;
+        }
+        
+    }
+
+    has_hit
+}
+
+fn new_point(rng: &mut Rng, players: &[Player], field: Rect) -> Point2{
+    let mut valid_position = false;
+
+    let mut new_x = 0.0;
+    let mut new_y = 0.0;
+
+    while !valid_position{
+        let mut valid_spaceship_pos = false;
+        while !valid_spaceship_pos{
+            new_x = rng.range(field.left() + ASTEROID_MAX_SIZE, field.right() - ASTEROID_MAX_SIZE);
+            new_y = rng.range(field.bottom() + ASTEROID_MAX_SIZE, field.top() - ASTEROID_MAX_SIZE);
+        
+            /* Keep clear of every ship, including ones waiting to respawn */
+            let clear_of = |position: Point2| {
+                let left_edge:bool = new_x < position.x - SPACESHIP_WIDTH;
+                let right_edge:bool = new_x > position.x + SPACESHIP_WIDTH;
+                let top_edge:bool = new_y > position.y + SPACESHIP_HEIGHT;
+                let bottom_edge:bool = new_y < position.y - SPACESHIP_HEIGHT;
+                (left_edge || right_edge) && (top_edge || bottom_edge)
+            };
+            if players.iter().all(|player| clear_of(player.position) && clear_of(player.spawn))
+            {
+                valid_spaceship_pos = true;
+                valid_position = true;
+            }
+        }
+    }
+
+    pt2(new_x, new_y)
+}
+
+fn generate_asteroid(rng: &mut Rng, position: Point2, num_points: u32, min_size: f32, max_size: f32, speed: f32, fragment: bool) -> Asteroid{
+    let new_size = rng.range(min_size, max_size);
+    
+    let new_speed = rng.range(ASTEROID_MIN_SPEED, ASTEROID_MAX_SPEED);
+
+    let mut thickness = ASTEROID_THICKNESS;
+    if fragment{
+        thickness *= 0.4;
+    }
+    let rotation = rng.range(0.0, std::f32::consts::PI * 2.0);
+    let mut asteroid = Asteroid{
+        position,
+        rotation: 0.0,
+        previous_position: position,
+        previous_rotation: 0.0,
+        rotation_speed: deg_to_rad(new_speed),
+        size: new_size,
+        points: Vec::new(),
+        num_points,
+        thickness,
+        velocity: ship::heading(rotation) * speed,
+        fragment,
+        hp: 1 + (new_size / ASTEROID_SIZE_PER_HIT_POINT) as u32,
+    };
+    let angle_inc:f32 = (360 / asteroid.num_points) as f32;
+    for i in 0..asteroid.num_points{
+        let angle = deg_to_rad(i as f32 * angle_inc);
+        let radius = asteroid.size / 2.0;
+
+        let real_radius = rng.range( radius * 0.80, radius * 1.20);
+
+        let x = angle.sin() * real_radius;
+        let y = angle.cos() * real_radius;
+        
+        asteroid.points.push(pt2(x,y));
+    }
+
+    /* Connect the last dot */
+    asteroid.points.push(asteroid.points[0]);
+    asteroid
+}
+
+/* Remember where everything was so the views can blend towards the new step */
+fn store_previous(world: &mut World){
+    for player in &mut world.players{
+        player.previous_position = player.position;
+        player.previous_rotation = player.rotation;
+        for missile in &mut player.missile{
+            missile.previous_position = missile.position;
+        }
+    }
+    for asteroid in &mut world.asteroid{
+        asteroid.previous_position = asteroid.position;
+        asteroid.previous_rotation = asteroid.rotation;
+    }
+}
+
+/* Position between the last two steps, snapping instead when it wrapped round the window */
+/* The window over the world, following one ship round a large one */
+pub fn view_of(world: &World, follow: usize, alpha: f32, screen: Rect) -> View{
+    let centre = match world.players.get(follow){
+        Some(player) if world.large => interpolate(player.previous_position, player.position, alpha),
+        _ => pt2(0.0, 0.0),
+    };
+    View{ centre, field: world.field, screen }
+}
+
+pub fn interpolate(previous: Point2, current: Point2, alpha: f32) -> Point2{
+    let delta = current - previous;
+    if delta.x.abs() > WINDOW_SIZE.0 as f32 / 2.0 || delta.y.abs() > WINDOW_SIZE.1 as f32 / 2.0{
+        current
+    }
+    else{
+        previous + delta * alpha
+    }
+}
+
+/* A shield takes the hit in place of the ship */
+fn shield_or_crash(world: &mut World, index: usize){
+    if powerup::absorb(&mut world.players[index]){
+        log::debug!(target: logging::COLLISION, "Shield down!");
+        world.effects.push(Effect::Spark(world.players[index].position));
+    }
+    else{
+        crash_player(world, index);
+    }
+}
+
+/* Breaks up one ship, the game only ends once nobody has a ship left */
+fn crash_player(world: &mut World, index: usize){
+    let player = &mut world.players[index];
+    player.alive = false;
+    player.thrust = false;
+    player.rotation_inc = 0.0;
+    player.lives = player.lives.saturating_sub(1);
+    player.respawn = RESPAWN_DELAY;
+    world.difficulty.record_death();
+    world.effects.push(Effect::Crashed(index));
+}
+
+fn total_score(players: &[Player]) -> u32{
+    players.iter().map(|player| player.score).sum()
+}
+
+
+/* One fixed step of play with one input per ship, missing inputs count as idle */
+pub fn world_step(world: &mut World, inputs: &[Input]) {
+    let win = world.field;
+    store_previous(world);
+    world.frame += 1;
+
+    world.difficulty.tick(TIMESTEP, total_score(&world.players));
+
+    build_broadphase(&mut world.broadphase, &world.asteroid);
+
+    /* First, has anyone crashed into anything? */
+    for i in 0..world.players.len(){
+        let player = &world.players[i];
+        if player.alive && player.invulnerable.is_zero() && has_ship_hit_asteroid(player, &world.asteroid, &world.broadphase, win){
+            shield_or_crash(world, i);
+        }
+    }
+
+    if world.friendly_fire{
+        for i in friendly_fire_hits(&mut world.players){
+            log::debug!(target: logging::COLLISION, "Friendly fire!");
+            shield_or_crash(world, i);
+        }
+    }
+
+    for i in 0..world.players.len(){
+        let input = inputs.get(i).copied().unwrap_or_default();
+        if world.players[i].alive{
+            steer(&mut world.players[i], input, world.handling.turn_rate);
+            if input.switch{
+                weapon::switch(&mut world.players[i], &world.arsenal);
+            }
+            let player = &mut world.players[i];
+            player.fire_queued |= input.fire;
+            if player.reload.is_zero() && (player.fire_queued || (input.trigger && player.powers.rapid())){
+                player.fire_queued = false;
+                fire_missile(world, i);
+            }
+            player_update(&mut world.players[i], &world.handling, win);
+        }
+        else{
+            respawn_update(&mut world.players[i]);
+        }
+    }
+
+    world_update(world);
+}
+
+/* Held keys become the ship's controls for this step, a stick turns in proportion to how far it's pushed */
+fn steer(player: &mut Player, input: Input, turn_rate: f32){
+    player.rotation_inc = match (input.turn, input.left, input.right){
+        (0, true, false) => deg_to_rad(turn_rate),
+        (0, false, true) => deg_to_rad(-turn_rate),
+        (0, _, _) => 0.0,
+        (turn, _, _) => deg_to_rad(turn_rate * turn as f32 / i8::MAX as f32),
+    };
+    player.thrust = input.thrust;
+}
+
+fn respawn_update(player: &mut Player){
+    if player.lives == 0{
+        return;
+    }
+
+    player.respawn = player.respawn.saturating_sub(TIMESTEP);
+    if player.respawn.is_zero(){
+        let missile = std::mem::take(&mut player.missile);
+        *player = Player{ missile, score: player.score, weapon: player.weapon, ..new_player(player.spawn, player.lives) };
+        player.invulnerable = RESPAWN_INVULNERABLE;
+    }
+}
+
+fn player_update(player: &mut Player, handling: &Handling, win: Rect) {
+    player.invulnerable = player.invulnerable.saturating_sub(TIMESTEP);
+    player.powers.tick();
+    player.reload = player.reload.saturating_sub(TIMESTEP);
+
+    ship::integrate(player, handling, TIMESTEP.as_secs_f32());
+
+    /* Handle wrapping across boundaries for space ship */
+    player.position = wrap_point(player.position, win);
+}
+
+/* Everything other than the ships, keeps running while the last ship breaks up */
+fn world_update(world: &mut World) {
+    let win = world.field;
+
+    hazard::well_update(world);
+
+    for asteroid in &mut world.asteroid{
+        asteroid.position = wrap_point(asteroid.position, win);
+        asteroid.rotation += asteroid.rotation_speed;
+        asteroid.position += asteroid.velocity;
+    }
+    
+    let mut fragments:Vec<Asteroid> = Vec::new();
+
+    if world.rocks.enabled{
+        build_broadphase(&mut world.broadphase, &world.asteroid);
+        let cracked = rocks::collide_asteroids(&mut world.asteroid, &world.broadphase, &world.rocks, win);
+        for &i in &cracked{
+            log::debug!(target: logging::COLLISION, "Crack!");
+            world.effects.push(Effect::Cracked(world.asteroid[i].clone()));
+            split_asteroid(&mut world.rng, &world.asteroid[i], &mut fragments);
+        }
+
+        let mut index = 0;
+        world.asteroid.retain(|_| {
+            let keep = !cracked.contains(&index);
+            index += 1;
+            keep
+        });
+    }
+
+    build_broadphase(&mut world.broadphase, &world.asteroid);
+
+    /* Whoever hit a rock first this step gets the points if it breaks */
+    let mut hit_by: Vec<Option<usize>> = vec![None; world.asteroid.len()];
+    let mut damage = vec![0; world.asteroid.len()];
+    for (owner, player) in world.players.iter_mut().enumerate(){
+        let dealt = missile_hits(&mut player.missile, &world.asteroid, &world.broadphase, win);
+        for ((by, total), &amount) in hit_by.iter_mut().zip(&mut damage).zip(&dealt).filter(|(_, &amount)| amount > 0){
+            *by = by.or(Some(owner));
+            *total += amount;
+        }
+
+        for missile in player.missile.iter().filter(|missile| missile.hit){
+            world.effects.push(Effect::Spark(missile.position));
+        }
+    }
+
+    for (asteroid, &amount) in world.asteroid.iter_mut().zip(&damage){
+        asteroid.hp = asteroid.hp.saturating_sub(amount);
+    }
+    let destroyed_by: Vec<Option<usize>> = world.asteroid.iter()
+        .zip(&hit_by)
+        .map(|(asteroid, by)| by.filter(|_| asteroid.hp == 0))
+        .collect();
+
+    for (asteroid, owner) in world.asteroid.iter().zip(&destroyed_by).filter_map(|(asteroid, by)| by.map(|owner| (asteroid, owner))){
+        world.effects.push(Effect::Destroyed(asteroid.clone()));
+        destroy_asteroid(&mut world.rng, asteroid, &mut world.players[owner].score, &mut fragments, &mut world.pickups);
+        world.difficulty.record_hit();
+    }
+
+    let mut index = 0;
+    world.asteroid.retain(|_| {
+        let keep = destroyed_by[index].is_none();
+        index += 1;
+        keep
+    });
+
+    for asteroid in fragments{
+        world.asteroid.push(asteroid);
+    }
+
+    for player in &mut world.players{
+        /* The edge of a one-screen world is the edge of the screen, so shots stop
+         * there. A large world has no edge you can see, so they wrap like the rest. */
+        if world.large{
+            player.missile.retain(|missile| !missile.hit && !missile.life.is_zero());
+        }
+        else{
+            player.missile.retain(|missiles| !has_missile_hit_edge(missiles, win) && !missiles.life.is_zero());
+        }
+        
+        for missile in &mut player.missile{
+            weapon::fly(missile, &world.arsenal.weapons[missile.weapon], &world.asteroid, world.large.then_some(win));
+            if world.large{
+                missile.position = wrap_point(missile.position, win);
+            }
+        }
+    }
+
+    powerup::pickup_update(world);
+
+    match world.mode{
+        GameMode::Endless => {
+            /* Generate new asteroid if needed */
+            if world.asteroid.len() < world.crowd(world.difficulty.max_asteroids) && world.calm.is_zero()
+            {
+                let new_pt =  new_point(&mut world.rng, &world.players, win);
+                let asteroid = generate_asteroid(&mut world.rng, new_pt, 8, ASTEROID_MIN_SIZE, ASTEROID_MAX_SIZE, world.difficulty.asteroid_speed, false);
+
+                world.asteroid.push(asteroid);
+            }
+        }
+        GameMode::Waves => wave::wave_update(world, TIMESTEP, win),
+    }
+}
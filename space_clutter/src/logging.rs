@@ -0,0 +1,76 @@
+use log::{LevelFilter, Log, Metadata, Record};
+use std::time::Instant;
+
+/* Filters as `level` or `target=level`, comma separated, the longest matching
+ * target wins. `SPACE_CLUTTER_LOG=info,space_clutter::collision=debug` shows
+ * hits and crashes on top of the usual. */
+pub const LOG_VAR: &str = "SPACE_CLUTTER_LOG";
+const DEFAULT_FILTER: &str = "info";
+
+/* Targets that don't follow a module, for things spread across several */
+pub const AUDIO: &str = "space_clutter::audio";
+pub const COLLISION: &str = "space_clutter::collision";
+pub const INPUT: &str = "space_clutter::input";
+
+struct Logger{
+    start: Instant,
+    default: LevelFilter,
+    targets: Vec<(String, LevelFilter)>,
+}
+
+impl Logger{
+    fn level(&self, target: &str) -> LevelFilter{
+        let matches = |prefix: &str| target == prefix || target.strip_prefix(prefix).is_some_and(|rest| rest.starts_with("::"));
+        self.targets.iter()
+            .filter(|(prefix, _)| matches(prefix))
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map_or(self.default, |&(_, level)| level)
+    }
+}
+
+impl Log for Logger{
+    fn enabled(&self, metadata: &Metadata) -> bool{
+        metadata.level() <= self.level(metadata.target())
+    }
+
+    fn log(&self, record: &Record){
+        if self.enabled(record.metadata()){
+            eprintln!("{:>9.3} {:<5} {}  {}", self.start.elapsed().as_secs_f32(), record.level(), record.target(), record.args());
+        }
+    }
+
+    fn flush(&self){}
+}
+
+/* Anything that doesn't parse is reported once the logger is up rather than dropped quietly */
+fn parse(filter: &str, logger: &mut Logger) -> Vec<String>{
+    let mut bad = Vec::new();
+    for part in filter.split(',').map(str::trim).filter(|part| !part.is_empty()){
+        match part.split_once('='){
+            Some((target, level)) => match level.trim().parse(){
+                Ok(level) => logger.targets.push((target.trim().to_string(), level)),
+                Err(_) => bad.push(part.to_string()),
+            },
+            None => match part.parse(){
+                Ok(level) => logger.default = level,
+                Err(_) => bad.push(part.to_string()),
+            },
+        }
+    }
+    bad
+}
+
+/* Sets up logging to stderr from the environment, once at startup */
+pub fn init(){
+    let mut logger = Logger{ start: Instant::now(), default: LevelFilter::Info, targets: Vec::new() };
+    let filter = std::env::var(LOG_VAR).unwrap_or_else(|_| DEFAULT_FILTER.to_string());
+    let bad = parse(&filter, &mut logger);
+
+    let max = logger.targets.iter().map(|&(_, level)| level).fold(logger.default, Ord::max);
+    if log::set_logger(Box::leak(Box::new(logger))).is_ok(){
+        log::set_max_level(max);
+    }
+    for part in bad{
+        log::warn!("{} has {:?}, which isn't a level or target=level", LOG_VAR, part);
+    }
+}
@@ -2,109 +2,183 @@ use nannou::prelude::*;
 use nannou::text::Font;
 use nannou_audio as audio;
 use nannou_audio::Buffer;
+use crossbeam_queue::ArrayQueue;
+use std::sync::Arc;
 use std::time::{Duration, Instant};
 
-const WINDOW_SIZE: (u32, u32) = (640, 480);
-const SPACESHIP_PEAK: f32 = 16.25;
-const SPACESHIP_TROUGH: f32 = 6.5;
-const SPACESHIP_WIDTH: f32 = 30.0;
-const SPACESHIP_HEIGHT: f32 = 39.0;
-const SPACESHIP_SPEED: f32 = 3.0;
-const ANGLE_INC: f32 = 3.6;
-const MISSILE_SPEED: f32 = 8.0;
-const MISSILE_SIZE: f32 = 4.0;
-
-/* Can have more than this, for example when a big asteroid explodes into little ones
- * however, this is used to prevent the game generating more */
-const MAX_ASTEROIDS: u32 = 10;
-const ASTEROID_MAX_SIZE: f32 = 80.0;
-const ASTEROID_MIN_SIZE: f32 = 40.0;
-const ASTEROID_MAX_SPEED: f32 = 4.0;
-const ASTEROID_MIN_SPEED: f32 = -4.0;
-const ASTEROID_THICKNESS: f32 = 5.0;
-const ASTEROID_SPEED: f32 = 0.5;
-
+mod camera;
+mod controls;
+mod debug;
+mod gamepad;
+mod menu;
+mod particles;
+mod settings;
+
+use camera::{Camera, CameraSettings};
+use controls::{Action, Bindings, Command};
+use debug::Debug;
+use gamepad::{Gamepads, PadEvent};
+use menu::{Cursor, Nav};
+use particles::Particles;
+use settings::{Item, Settings};
+use space_clutter::difficulty::{Difficulty, Preset, Presets};
+use space_clutter::net::{self, Session};
+use space_clutter::pilot::{self, Pilot};
+use space_clutter::powerup::{PICKUP_RADIUS, PICKUP_WARNING};
+use space_clutter::replay::{self, Playback, Replay};
+use space_clutter::scores::{self, Entry, HighScores};
+use space_clutter::wave::GameMode;
+use space_clutter::weapon::{Arsenal, Shape};
+use space_clutter::{config, logging, rng, ship, spatial, stress};
+use space_clutter::spatial::View;
+use space_clutter::{interpolate, ship_hull, view_of, world_step, Asteroid, Effect, Input, Player, StateEvents, World};
+use space_clutter::{ASTEROID_MAX_SIZE, SPACESHIP_HEIGHT, TIMESTEP, WINDOW_SIZE};
+
+/* Frame time beyond this is dropped rather than simulated, avoids a spiral of death */
+const MAX_FRAME_TIME: Duration = Duration::from_millis(250);
+/* Keys are ignored this long after the game over screen appears */
+const GAMEOVER_INPUT_LOCKOUT: Duration = Duration::from_millis(1000);
+/* Ships blink on and off this often while invulnerable */
+const INVULNERABLE_BLINK_MS: u128 = 100;
+/* Keyboard and pads share one machine between this many ships */
+const LOCAL_PLAYERS: usize = 2;
+/* Shields and pickups start blinking when this close to running out */
+const SHIELD_WARNING: Duration = Duration::from_secs(2);
+/* Gravity wells are drawn as rings falling in from this far out, each taking
+ * this many steps to reach the horizon */
+const WELL_RINGS: u32 = 3;
+const WELL_RING_REACH: f32 = 60.0;
+const WELL_RING_STEPS: f32 = 90.0;
+/* Width of the large world's radar, its height keeps the world's proportions */
+const RADAR_WIDTH: f32 = 120.0;
+/* The title screen swaps between the title and the high scores this often */
+const MENU_PAGE_TIME: Duration = Duration::from_secs(6);
+/* Up and Down step through these when entering initials */
+const INITIALS_ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ ";
+/* Music plays this much quieter under the pause menu */
+const PAUSED_MUSIC_VOLUME: f32 = 0.3;
+const PAUSE_ITEMS: [&str; 4] = ["RESUME", "RESTART", "OPTIONS", "QUIT TO TITLE"];
+const PAUSE_OPTIONS_ITEM: usize = 2;
+/* Audio thread messages waiting for the next frame to log them, any past this are dropped */
+const AUDIO_LOG_CAPACITY: usize = 64;
 
 #[derive(Copy,Clone)]
 enum State{
     Idle, // Normal game 
+    Dying, // Ship is breaking up, the rest of the world carries on
     GameOver,
     Menu,
+    Initials, // A ship made the high-score table and is signing it
+    Paused, // Play is frozen under the pause menu
+    Options,
 }
 
-#[derive(Copy,Clone)]
-enum StateEvents{
-    NoneKeyPress,
-    LeftKeyPress,
-    LeftKeyRelease,
-    RightKeyPress,
-    RightKeyRelease,
-    UpKeyPress,
-    UpKeyRelease,
-    SpaceKeyPress,
-    SpaceKeyRelease,
-}
-
-
-struct Player{
-    position: Point2,
-    rotation: f32,
-    rotation_inc: f32,
-    score: u32,
-    thrust: bool,
-    thrust_rotation: f32,
-    thrust_counter: u32,
-    missile: Vec<Projectile>,
-}
-
-struct Asteroid{
-    position: Point2,
-    rotation: f32,
-    rotation_speed: f32,
-    size: f32,
-    num_points: u32,
-    fragment: bool, // Whether it is a fragment or not
-    thickness: f32,
-    thrust_rotation: f32,
-    points: Vec<Point2>,
-    speed: f32,
-}
-
-struct Projectile{
-    hit: bool,
-    position: Point2,
-    rotation: f32,
+/* Where the pause menu's cursor is, and what to go back to */
+struct Pause{
+    resume: State,
+    selection: usize,
+}
+
+/* The options menu goes back to wherever it was opened from */
+struct Options{
+    from: State,
+    cursor: Cursor,
+    /* Only a changed binding writes the player's own controls file */
+    rebound: bool,
 }
 
+/* Letters picked so far for the first ship in the queue, the rest wait their turn */
+struct Initials{
+    players: Vec<usize>,
+    letters: [usize; scores::INITIALS_LENGTH],
+    cursor: usize,
+}
+
+
 struct Model {
-    player: Player,
-    asteroid: Vec<Asteroid>,
+    world: World,
     last_event: WindowEvent,
     game_state:State,
     raw_font: Vec<u8>,
     score_font: Vec<u8>,
     credit_font: Vec<u8>,
+    /* Drawn every frame while the overlay shows, so it's built once */
+    debug_font: Font,
     stream: audio::Stream<Audio>,
-    difficulty: Difficulty,
+    audio_log: Arc<ArrayQueue<&'static str>>,
     tick: Instant,
     display_text: bool,
+    accumulator: Duration,
+    alpha: f32,
+    particles: Particles,
+    dying: Duration,
+    input_lockout: Duration,
+    camera: Camera,
+    coop: bool,
+    gamepads: Gamepads,
+    inputs: Vec<Input>,
+    net: Option<Session>,
+    /* Why the last networked game ended early, shown on the title screen until the next one */
+    notice: Option<String>,
+    fixed_seed: Option<u64>,
+    recording: Option<Replay>,
+    playback: Option<Playback>,
+    attract: World,
+    pilot: Pilot,
+    scores: HighScores,
+    initials: Option<Initials>,
+    /* Places on the table this game earned, ship then place, shown with the score */
+    placed: Vec<(usize, usize)>,
+    menu_time: Duration,
+    pause: Option<Pause>,
+    settings: Settings,
+    /* As last written, so the file is only touched when something changed */
+    saved_settings: Settings,
+    bindings: Bindings,
+    options: Option<Options>,
+    debug: Debug,
 }
 
 struct Audio{
     audio: audrey::read::BufFileReader,
     event: Vec<audrey::read::BufFileReader>,
     game_state:State,
-}
-
-struct Difficulty{
-    max_asteroids:u32,
-    asteroid_speed:f32,
-    tick: Instant,
-    duration: Duration,
+    master_volume: f32,
+    music_volume: f32,
+    sfx_volume: f32,
+    /* Turned down under the pause menu, on top of the player's own volumes */
+    duck: f32,
+    /* The callback can't block on the logger, so it leaves messages here for update() */
+    log: Arc<ArrayQueue<&'static str>>,
 }
 
 fn main() {
+    logging::init();
+
+    if std::env::args().any(|arg| arg == "--bench"){
+        stress::run_benchmark();
+        return;
+    }
+
+    if std::env::args().any(|arg| arg == "--replay-test"){
+        if !replay::run_replay_tests(){
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    if std::env::args().any(|arg| arg == "--gym-bench"){
+        space_clutter::gym::run_benchmark();
+        return;
+    }
+
+    if std::env::args().any(|arg| arg == "--soak"){
+        pilot::run_soak_test();
+        return;
+    }
+
     nannou::app(model)
+        .exit(exit)
         .event(event)
         .update(update)
         .view(view)
@@ -122,12 +196,19 @@ fn model(app: &App) -> Model {
         .build()
         .unwrap();
     
+    let settings = Settings::load();
     let audio_host = audio::Host::new();
     let theme = audrey::open("assets/space_clutter_theme.wav").expect("Not Found");
+    let audio_log = Arc::new(ArrayQueue::new(AUDIO_LOG_CAPACITY));
     let audio_data = Audio{ 
         game_state: State::Menu,
         audio: theme,
-        event: Vec::new()};
+        event: Vec::new(),
+        master_volume: settings.master_volume,
+        music_volume: settings.music_volume,
+        sfx_volume: settings.sfx_volume,
+        duck: 1.0,
+        log: audio_log.clone()};
 
     let stream = audio_host
         .new_output_stream(audio_data)
@@ -138,34 +219,58 @@ fn model(app: &App) -> Model {
         .unwrap();
 
     stream.play().unwrap();
-    
-    let model = Model {
-        player: Player {
-                position: pt2(0.0, 0.0),
-                rotation: 0.0,
-                rotation_inc: 0.0,
-                score: 0,
-                thrust: false,
-                thrust_rotation: 0.0,
-                thrust_counter: 0,
-                missile: Vec::new(),
-        },
-        asteroid: Vec::new(),
+
+    let world = World::new(Difficulty::new(Presets::load(), Preset::Normal, stress::max_asteroids()), rng::time_seed());
+    check_sounds(&world.arsenal);
+    /* The title screen plays its own game so nothing it does touches the real one */
+    let attract = world.clone();
+    let playback = replay::playback_from_args(&world);
+
+    let mut model = Model {
+        world,
         last_event: KeyReleased(Key::Escape),
         game_state: State::Menu,
         raw_font: include_bytes!("../assets/Kenney Mini.ttf").to_vec(),
         score_font: include_bytes!("../assets/Kenney Pixel.ttf").to_vec(),
         credit_font: include_bytes!("../assets/Kenney Mini.ttf").to_vec(),
+        debug_font: Font::from_bytes(include_bytes!("../assets/Kenney Mini.ttf").to_vec()).unwrap(),
         stream: stream,
-        difficulty:Difficulty{
-            max_asteroids: MAX_ASTEROIDS,
-            asteroid_speed: ASTEROID_SPEED,
-            tick: Instant::now(),
-            duration: Duration::new(5, 0),
-        },
+        audio_log,
         tick: Instant::now(),
         display_text: true,
+        accumulator: Duration::ZERO,
+        alpha: 0.0,
+        particles: Particles::new(),
+        dying: Duration::ZERO,
+        input_lockout: Duration::ZERO,
+        camera: Camera::new(CameraSettings::load()),
+        coop: false,
+        gamepads: Gamepads::new(),
+        inputs: vec![Input::default(); LOCAL_PLAYERS],
+        net: net::session_from_args(),
+        notice: None,
+        fixed_seed: rng::seed_from_args(),
+        recording: None,
+        playback,
+        attract,
+        pilot: Pilot::new(),
+        scores: HighScores::load(),
+        initials: None,
+        placed: Vec::new(),
+        menu_time: Duration::ZERO,
+        pause: None,
+        saved_settings: settings.clone(),
+        settings,
+        bindings: Bindings::load(),
+        options: None,
+        debug: Debug::new(),
     };
+    apply_settings(&mut model);
+
+    /* Networked games and replays skip the menu, there's nothing to pick */
+    if model.net.is_some() || model.playback.is_some(){
+        reset(app, &mut model);
+    }
 
     model
 }
@@ -173,7 +278,7 @@ fn model(app: &App) -> Model {
 fn reset_audio_loop(audio: &mut Audio){
     
     match audio.game_state{
-        State::Idle => audio.audio = audrey::open("assets/space_battle3.wav").expect("Not Found"),
+        State::Idle | State::Dying => audio.audio = audrey::open("assets/space_battle3.wav").expect("Not Found"),
         _ => audio.audio = audrey::open("assets/space_clutter_theme.wav").expect("Not Found"),
     }
 }
@@ -185,13 +290,13 @@ fn audio(audio:&mut Audio, buffer: &mut Buffer){
     let frames_available = buffer.len_frames();
     for (frame, file_frame) in buffer.chunks_mut(2).zip(file_frames) {
         for (sample, &file_sample) in frame.iter_mut().zip(&file_frame) {
-            *sample = file_sample/2.0;
+            *sample = file_sample/2.0 * audio.master_volume * audio.music_volume * audio.duck;
         }
         frames_written += 1;
     }
 
     if frames_written < frames_available{
-        println!("Restart audio loop");
+        audio.log.push("Restart audio loop").ok();
         reset_audio_loop(audio);
     }
     let mut event_ended = Vec::new();
@@ -201,12 +306,12 @@ fn audio(audio:&mut Audio, buffer: &mut Buffer){
         let frames_available = buffer.len_frames();
         for (frame, file_frame) in buffer.chunks_mut(2).zip(file_frames) {
             for (sample, &file_sample) in frame.iter_mut().zip(&file_frame) {
-                *sample += file_sample /2.0;
+                *sample += file_sample /2.0 * audio.master_volume * audio.sfx_volume;
             }
             frames_written += 1;
         }
         if frames_written < frames_available{
-            println!("Pop audio event");
+            audio.log.push("Pop audio event").ok();
             event_ended.push(i);
         }
     }
@@ -216,333 +321,607 @@ fn audio(audio:&mut Audio, buffer: &mut Buffer){
     }
 } 
 
+
 fn reset(_app: &App, model: &mut Model){
-    model.player.position = pt2(0.0, 0.0);
-    model.player.rotation = 0.0;
-    model.player.rotation_inc = 0.0;
-    model.player.score = 0;
-    model.player.thrust = false;
-    model.player.thrust_rotation = 0.0;
-    model.player.thrust_counter = 0;
-    model.player.missile = Vec::new();
-    
-    model.asteroid = Vec::new();
+    /* Peers have to agree on the seed, so a networked game never picks its own */
+    if let Some(session) = &mut model.net{
+        model.world.restart(session.players(), model.fixed_seed.unwrap_or(net::SESSION_SEED));
+        /* The other peers check this against their own before taking any input from us */
+        session.start(&replay::setup_of(&model.world));
+    }
+    else if let Some(playback) = &model.playback{
+        replay::apply(&playback.replay.setup, &mut model.world);
+    }
+    else{
+        /* One picked on the title screen is kept from here on */
+        save_settings(model);
+        /* A preset picked from the pause menu waits for the next game, replays need it fixed from the start */
+        model.world.difficulty.set_preset(model.settings.preset());
+        model.world.restart(if model.coop { 2 } else { 1 }, model.fixed_seed.unwrap_or_else(rng::time_seed));
+    }
+    log::info!("Seed {}", model.world.seed);
+    model.notice = None;
+
+    /* Local games are always recorded, networked ones can't be replayed on their own */
+    model.recording = if model.net.is_none() && model.playback.is_none(){
+        Some(Replay::new(replay::setup_of(&model.world)))
+    }
+    else{
+        None
+    };
+    model.inputs = vec![Input::default(); LOCAL_PLAYERS];
+
     model.last_event = KeyReleased(Key::Escape);
     model.game_state = State::Idle;
+    model.placed.clear();
+    model.pause = None;
+    model.options = None;
 
-    model.difficulty.max_asteroids = MAX_ASTEROIDS;
-    model.difficulty.asteroid_speed = ASTEROID_SPEED;
+    model.particles.clear();
+    model.camera.reset();
     
     model.stream.send( move |audio| {audio.game_state=State::Idle}).ok();
     model.stream.send( move |audio| {audio.audio=audrey::open("assets/space_battle3.wav").expect("Not Found")}).ok();
+    set_duck(&model.stream, 1.0);
 }
 
-fn event(_app: &App, _model: &mut Model, _event: Event) { }
+fn set_duck(stream: &audio::Stream<Audio>, volume: f32){
+    stream.send( move |audio| {audio.duck=volume}).ok();
+}
 
-fn keypress_to_state(key: Key) -> StateEvents{
-    match key{
-        Key::Left => return StateEvents::LeftKeyPress,
-        Key::Right => return StateEvents::RightKeyPress,
-        Key::Up => return StateEvents::UpKeyPress,
-        Key::Space => return StateEvents::SpaceKeyPress,
-        _ => return StateEvents::NoneKeyPress,
+/* Puts the settings into effect straight away. The difficulty can't change
+ * under a game in progress, so that one waits for the next reset, and peers
+ * and replays bring their own. */
+fn apply_settings(model: &mut Model){
+    let (master, music, sfx) = (model.settings.master_volume, model.settings.music_volume, model.settings.sfx_volume);
+    model.stream.send( move |audio| {
+        audio.master_volume = master;
+        audio.music_volume = music;
+        audio.sfx_volume = sfx;
+    }).ok();
+
+    model.camera.shake = model.settings.shake;
+    model.camera.hit_stop = model.settings.hit_stop;
+    model.camera.flash_enabled = model.settings.flash;
+
+    if model.pause.is_none() && model.net.is_none() && model.playback.is_none(){
+        model.world.difficulty.set_preset(model.settings.preset());
     }
 }
 
-fn keyrelease_to_state(key: Key) -> StateEvents{
-    match key{
-        Key::Left => return StateEvents::LeftKeyRelease,
-        Key::Right => return StateEvents::RightKeyRelease,
-        Key::Up => return StateEvents::UpKeyRelease,
-        Key::Space => return StateEvents::SpaceKeyRelease,
-        _ => return StateEvents::NoneKeyPress,
+fn open_options(model: &mut Model){
+    model.options = Some(Options{ from: model.game_state, cursor: Cursor::default(), rebound: false });
+    model.game_state = State::Options;
+}
+
+fn save_settings(model: &mut Model){
+    if model.settings != model.saved_settings{
+        model.settings.save();
+        model.saved_settings = model.settings.clone();
     }
 }
 
+/* Settings are written out once on the way back rather than on every change */
+fn close_options(model: &mut Model){
+    if let Some(options) = model.options.take(){
+        model.game_state = options.from;
+        save_settings(model);
+        if options.rebound{
+            model.bindings.save();
+        }
+    }
+}
+
+/* Freezes play under the pause menu. A networked game can't stop the other
+ * peers, so it carries on regardless. */
+fn pause(model: &mut Model){
+    if model.net.is_some() || !matches!(model.game_state, State::Idle | State::Dying){
+        return;
+    }
+
+    model.pause = Some(Pause{ resume: model.game_state, selection: 0 });
+    model.game_state = State::Paused;
+    set_duck(&model.stream, PAUSED_MUSIC_VOLUME);
+}
+
+/* Controls let go while paused would otherwise stay held, so the ships start again with nothing pressed */
+fn resume(model: &mut Model){
+    if let Some(pause) = model.pause.take(){
+        model.game_state = pause.resume;
+        model.inputs = vec![Input::default(); LOCAL_PLAYERS];
+        model.last_event = KeyReleased(Key::Escape);
+        model.accumulator = Duration::ZERO;
+        set_duck(&model.stream, 1.0);
+    }
+}
+
+/* Leaves the game unfinished, so there's no replay to keep and no score to sign */
+fn quit_to_title(model: &mut Model){
+    model.pause = None;
+    model.recording = None;
+    model.game_state = State::Menu;
+    model.menu_time = Duration::ZERO;
+    model.particles.clear();
+    model.camera.reset();
+
+    model.stream.send( move |audio| {audio.game_state=State::Menu}).ok();
+    model.stream.send( move |audio| {audio.audio=audrey::open("assets/space_clutter_theme.wav").expect("Not Found")}).ok();
+    set_duck(&model.stream, 1.0);
+    apply_settings(model);
+}
+
+fn event(_app: &App, _model: &mut Model, _event: Event) { }
+
+/* Anything changed on the title screen and not yet played with is kept */
+fn exit(_app: &App, mut model: Model){
+    save_settings(&mut model);
+}
+
 fn window_event(app: &App, model: &mut Model, event: WindowEvent)
 {
+    /* A key being bound goes to the options menu, even one the overlay uses */
+    let capturing = model.options.as_ref().is_some_and(|options| options.cursor.capturing);
+    if let KeyPressed(key) = event{
+        if !capturing && debug_key(model, key){
+            return;
+        }
+    }
+
+    /* The pause key or clicking away from the window stops play */
+    let pause_key = matches!(event, KeyPressed(key) if model.bindings.key(key).is_some_and(|(_, action)| action == Action::Pause));
+    if matches!(model.game_state, State::Idle | State::Dying) && (pause_key || event == Unfocused){
+        pause(model);
+        return;
+    }
+
     match model.game_state{
+        State::Idle | State::Dying if model.playback.is_some() => replay_event(model, event),
         State::Idle => idle_event(app, model, event),
-        State::GameOver => menu_event(app, model, event),
+        State::Dying => {},
+        State::GameOver => gameover_event(app, model, event),
         State::Menu => menu_event(app, model, event),
+        State::Initials => initials_event(model, event),
+        State::Paused => pause_event(app, model, event),
+        State::Options => options_event(model, event),
     }
 }
 
-fn menu_event(app: &App, model: &mut Model, event: WindowEvent)
-{
-    match event {
-        KeyPressed(_key) => { reset(app, model) }
-        _ => {}
+/* F3 shows the overlay anywhere. Freezing, stepping and the time scale only
+ * work with it showing, and never in a networked game where the peers would
+ * run on without us. */
+fn debug_key(model: &mut Model, key: Key) -> bool{
+    let clock = model.debug.on && model.net.is_none();
+    match key{
+        Key::F3 => model.debug.toggle(),
+        Key::F5 if clock => model.debug.frozen ^= true,
+        Key::F6 if clock && model.debug.frozen => model.debug.step = true,
+        Key::F7 if clock => model.debug.slower(),
+        Key::F8 if clock => model.debug.faster(),
+        _ => return false,
     }
+    true
 }
 
-fn idle_event(_app: &App, model: &mut Model, event: WindowEvent)
+/* While waiting on a key to bind, the next one pressed is taken, Escape backs out */
+fn options_event(model: &mut Model, event: WindowEvent)
 {
-    if model.last_event != event
-    {
-        match event {
-            KeyPressed(key) => { println!("Key Pressed"); handle_event(model, keypress_to_state(key)) }
-            KeyReleased(key) => { println!("Key Released");handle_event(model, keyrelease_to_state(key)) }
-            _ => {}
+    let capturing = model.options.as_ref().is_some_and(|options| options.cursor.capturing);
+    if capturing{
+        match event{
+            KeyPressed(Key::Escape) => {}
+            KeyPressed(key) if controls::bindable(key) => {
+                let items = model.settings.items(&model.bindings);
+                if let Some(options) = &mut model.options{
+                    if let Item::Bind(player, action) = items[options.cursor.selection]{
+                        model.bindings.bind(player, action, key);
+                        options.rebound = true;
+                    }
+                }
+            }
+            _ => return,
         }
-        model.last_event = event;
+        if let Some(options) = &mut model.options{
+            options.cursor.capturing = false;
+        }
+        return;
+    }
+
+    if let Some(nav) = key_nav(model, &event){
+        options_input(model, nav);
     }
 }
 
-fn fire_missile(model: &mut Model)
-{
-    println!("Firing missile");
-    let missile = Projectile{
-        hit: false,
-        position: model.player.position,
-        rotation: model.player.rotation,
+/* Up and Down pick a row, Left and Right change it. Space flips a toggle,
+ * starts listening for a key or goes back, as the row needs. */
+fn options_input(model: &mut Model, nav: Nav){
+    let options = match &mut model.options{
+        Some(options) => options,
+        None => return,
     };
-    model.player.missile.push(missile);
-
-    let sound = audrey::open("assets/space_clutter_laser.wav").expect("Not Found");
 
-    model.stream.send( move |audio| {audio.event.push(sound)}).ok();
+    let items = model.settings.items(&model.bindings);
+    let item = items[options.cursor.selection];
+    let before = model.settings.clone();
+    match nav{
+        Nav::Up => options.cursor.up(items.len()),
+        Nav::Down => options.cursor.down(items.len()),
+        Nav::Left => model.settings.adjust(item, -1),
+        Nav::Right => model.settings.adjust(item, 1),
+        Nav::Select => match item{
+            Item::Bind(..) => options.cursor.capturing = true,
+            Item::Back => close_options(model),
+            _ => model.settings.adjust(item, 1),
+        },
+        Nav::Back => close_options(model),
+    }
+    if model.settings != before{
+        apply_settings(model);
+    }
 }
 
-fn has_missile_hit_edge(missile: &Projectile, win: Rect) -> bool{
-    let mut has_hit = false;
-
-    if missile.position.x > win.right()
-    {
-        has_hit = true;
+/* A key pressed in a menu, as the menus see it */
+fn key_nav(model: &Model, event: &WindowEvent) -> Option<Nav>{
+    match event{
+        KeyPressed(key) => model.bindings.nav(*key),
+        _ => None,
     }
-    else if missile.position.x < win.left()
-    {
-        has_hit = true;
+}
+
+fn pause_event(app: &App, model: &mut Model, event: WindowEvent)
+{
+    if let Some(nav) = key_nav(model, &event){
+        pause_input(app, model, nav);
     }
-    else if missile.position.y > win.top()
-    {
-        has_hit = true;
+}
+
+/* Up and Down move the cursor, Select picks. The pause key resumes, the same
+ * one that brought the menu up. */
+fn pause_input(app: &App, model: &mut Model, nav: Nav){
+    let pause = match &mut model.pause{
+        Some(pause) => pause,
+        None => return,
+    };
+
+    let count = PAUSE_ITEMS.len();
+    match nav{
+        Nav::Up => pause.selection = (pause.selection + count - 1) % count,
+        Nav::Down => pause.selection = (pause.selection + 1) % count,
+        Nav::Back => resume(model),
+        Nav::Select => match pause.selection{
+            0 => resume(model),
+            1 => reset(app, model),
+            PAUSE_OPTIONS_ITEM => open_options(model),
+            _ => quit_to_title(model),
+        },
+        _ => {}
     }
-    else if missile.position.y < win.bottom()
-    {
-        has_hit = true;
+}
+
+fn menu_event(app: &App, model: &mut Model, event: WindowEvent)
+{
+    /* The camera can only follow one ship round a large world, so co-op keeps to one
+     * screen. Turning either on turns the other off. */
+    let nav = key_nav(model, &event);
+    match (event, nav) {
+        (KeyPressed(Key::Key2), _) => {
+            model.coop ^= true;
+            if model.coop{
+                model.world.set_large(false);
+            }
+        }
+        (KeyPressed(Key::F), _) => { model.world.friendly_fire ^= true }
+        (KeyPressed(Key::L), _) => {
+            model.world.set_large(!model.world.large);
+            model.coop &= !model.world.large;
+        }
+        (KeyPressed(Key::O), _) => { open_options(model) }
+        (_, Some(Nav::Left)) => { model.settings.adjust(Item::Preset, -1); apply_settings(model) }
+        (_, Some(Nav::Right)) => { model.settings.adjust(Item::Preset, 1); apply_settings(model) }
+        (_, Some(Nav::Up | Nav::Down)) => { model.world.mode = model.world.mode.toggle() }
+        (KeyPressed(_key), _) => { reset(app, model) }
+        _ => {}
     }
+}
 
-    if missile.hit
-    {
-        has_hit = true;
+/* Ignores keys for a moment so one still held from play doesn't skip the score.
+ * A networked game can't be restarted from one end, so that stays put. */
+/* Any key retries with the same setup, changing it is for the title screen */
+fn gameover_event(app: &App, model: &mut Model, event: WindowEvent)
+{
+    if let KeyPressed(_) = event{
+        if model.input_lockout.is_zero() && model.net.is_none(){
+            reset(app, model);
+        }
     }
-    
-    if has_hit
-    {
-        println!("Removing missile from vector");
+}
+
+/* Up and Down pick the letter, typing one works too. Space, Return or Right
+ * moves on to the next letter and Left or Backspace goes back. */
+fn initials_event(model: &mut Model, event: WindowEvent)
+{
+    if !model.input_lockout.is_zero(){
+        return;
     }
 
-    has_hit
+    match event{
+        KeyPressed(Key::Back) => initials_input(model, Nav::Left),
+        KeyPressed(key) if !controls::letter(key) => {
+            if let Some(nav) = model.bindings.nav(key){
+                initials_input(model, nav);
+            }
+        }
+        ReceivedCharacter(c) if c.is_ascii_alphabetic() => {
+            if let Some(initials) = &mut model.initials{
+                let letter = c.to_ascii_uppercase() as u8;
+                initials.letters[initials.cursor] = INITIALS_ALPHABET.iter().position(|&l| l == letter).unwrap_or(0);
+            }
+            initials_input(model, Nav::Select);
+        }
+        _ => {}
+    }
 }
 
-fn has_missile_hit_asteroid(missiles: &mut Vec<Projectile>, asteroid: &Asteroid, score: &mut u32, fragment: &mut Vec<Asteroid>, stream: &mut audio::Stream<Audio>) -> bool{
-    let mut has_hit = false;
-
-        for missile in &mut *missiles
-        {
-            let left_edge:bool = (missile.position.x + (MISSILE_SIZE/2.0)) > (asteroid.position.x - (asteroid.size / 2.0));
-            let right_edge:bool = (missile.position.x + (MISSILE_SIZE/2.0)) < (asteroid.position.x + (asteroid.size / 2.0));
-            let top_edge:bool = (missile.position.y + (MISSILE_SIZE/2.0)) < (asteroid.position.y + (asteroid.size / 2.0));
-            let bottom_edge:bool = (missile.position.y + (MISSILE_SIZE/2.0)) > (asteroid.position.y - (asteroid.size / 2.0));
+/* Keys and pads share these, Up and Down step the letter. Back goes back a letter too. */
+fn initials_input(model: &mut Model, nav: Nav){
+    let initials = match &mut model.initials{
+        Some(initials) => initials,
+        None => return,
+    };
 
-            if left_edge && right_edge && top_edge && bottom_edge
-            {
-                println!("Hit!");
-                missile.hit = true;
-                has_hit = true;
-                *score += 1;
+    let count = INITIALS_ALPHABET.len();
+    let letter = &mut initials.letters[initials.cursor];
+    match nav{
+        Nav::Up => *letter = (*letter + 1) % count,
+        Nav::Down => *letter = (*letter + count - 1) % count,
+        Nav::Left | Nav::Back => initials.cursor = initials.cursor.saturating_sub(1),
+        Nav::Right | Nav::Select => {
+            if initials.cursor + 1 < scores::INITIALS_LENGTH{
+                initials.cursor += 1;
+            }
+            else{
+                sign_scores(model);
+            }
+        }
+    }
+}
 
-                let sound = audrey::open("assets/space_clutter_boom.wav").expect("Not Found");
-                stream.send( move |audio| {audio.event.push(sound)}).ok();
-               
-                if !asteroid.fragment{
-                    let new_point_l = pt2(asteroid.position.x - (asteroid.size / 2.0), asteroid.position.y);
-                    let asteroid_l = generate_asteroid(new_point_l, 12, ASTEROID_MIN_SIZE / 2.0, ASTEROID_MAX_SIZE / 2.0, ASTEROID_SPEED, true);
-                    
-                    let new_point_r = pt2(asteroid.position.x + (asteroid.size / 2.0), asteroid.position.y);
-                    let asteroid_r = generate_asteroid(new_point_r, 12, ASTEROID_MIN_SIZE / 2.0, ASTEROID_MAX_SIZE / 2.0, ASTEROID_SPEED, true);
+/* Puts the first waiting ship's initials on the table, then moves on to the
+ * next ship that still makes it, or the score screen once nobody does */
+fn sign_scores(model: &mut Model){
+    if let Some(mut initials) = model.initials.take(){
+        let player = initials.players.remove(0);
+        let entry = Entry{
+            initials: initials.letters.iter().map(|&i| INITIALS_ALPHABET[i] as char).collect(),
+            score: model.world.players[player].score,
+            mode: model.world.mode.name().to_string(),
+            preset: model.world.difficulty.preset.name().to_string(),
+        };
+        if let Some(place) = model.scores.insert(entry){
+            model.placed.push((player, place));
+        }
+        match model.scores.save(){
+            Ok(path) => log::info!("Saved high scores {}", path.display()),
+            Err(error) => log::warn!("Could not save high scores: {}", error),
+        }
+        model.initials = Some(initials);
+    }
+    else{
+        /* Only local games go on the table, a replay or a peer's game isn't yours to sign */
+        let local = model.net.is_none() && model.playback.is_none();
+        let players = if local { (0..model.world.players.len()).collect() } else { Vec::new() };
+        model.initials = Some(Initials{ players, letters: [0; scores::INITIALS_LENGTH], cursor: 0 });
+    }
 
-                    fragment.push(asteroid_l);
-                    fragment.push(asteroid_r); 
-                }
+    if let Some(initials) = &mut model.initials{
+        while let Some(&player) = initials.players.first(){
+            if model.scores.qualifies(model.world.players[player].score){
+                break;
             }
+            initials.players.remove(0);
         }
+        initials.letters = [0; scores::INITIALS_LENGTH];
+        initials.cursor = 0;
+    }
 
-    has_hit
+    model.input_lockout = GAMEOVER_INPUT_LOCKOUT;
+    if model.initials.as_ref().is_some_and(|initials| !initials.players.is_empty()){
+        model.game_state = State::Initials;
+    }
+    else{
+        model.initials = None;
+        model.game_state = State::GameOver;
+    }
 }
 
-fn has_ship_hit_asteroid(player: &Player, asteroids: &Vec<Asteroid>) -> bool{
-    let mut has_hit = false;
-
-    let true_rotation = player.rotation + deg_to_rad(90.0);
-    let true_x = player.position.x + (SPACESHIP_PEAK * true_rotation.cos());
-    let true_y = player.position.y + (SPACESHIP_PEAK * true_rotation.sin());
-    
-    let r_tail_x = player.position.x + ((SPACESHIP_WIDTH / 2.0) * true_rotation.cos());
-    let r_tail_y = player.position.y + (-(SPACESHIP_PEAK + SPACESHIP_TROUGH) * true_rotation.sin());
-    
-    let l_tail_x = player.position.x - ((SPACESHIP_WIDTH / 2.0) * true_rotation.cos());
-    let l_tail_y = player.position.y + (-(SPACESHIP_PEAK + SPACESHIP_TROUGH) * true_rotation.sin());
-
-    for asteroid in asteroids{
-        /* Has peak hit asteroid? */
-        let left_edge:bool = (true_x) > (asteroid.position.x - (asteroid.size / 2.0));
-        let right_edge:bool = (true_x) < (asteroid.position.x + (asteroid.size / 2.0));
-        let top_edge:bool = (true_y) < (asteroid.position.y + (asteroid.size / 2.0));
-        let bottom_edge:bool = (true_y) > (asteroid.position.y - (asteroid.size / 2.0));
-        
-        let l_left_edge:bool = (l_tail_x) > (asteroid.position.x - (asteroid.size / 2.0));
-        let l_right_edge:bool = (l_tail_x) < (asteroid.position.x + (asteroid.size / 2.0));
-        let l_top_edge:bool = (l_tail_y) < (asteroid.position.y + (asteroid.size / 2.0));
-        let l_bottom_edge:bool = (l_tail_y) > (asteroid.position.y - (asteroid.size / 2.0));
-        
-        let r_left_edge:bool = (r_tail_x) > (asteroid.position.x - (asteroid.size / 2.0));
-        let r_right_edge:bool = (r_tail_x) < (asteroid.position.x + (asteroid.size / 2.0));
-        let r_top_edge:bool = (r_tail_y) < (asteroid.position.y + (asteroid.size / 2.0));
-        let r_bottom_edge:bool = (r_tail_y) > (asteroid.position.y - (asteroid.size / 2.0));
-
-        if left_edge && right_edge && top_edge && bottom_edge
-        {
-            println!("CRASH!");
-            has_hit = true;
-        }
-        else if l_left_edge && l_right_edge && l_top_edge && l_bottom_edge
-        {
-            println!("LEFT CRASH!");
-            has_hit = true;
-        }
-        else if r_left_edge && r_right_edge && r_top_edge && r_bottom_edge
-        {
-            println!("RIGHT CRASH!");
-            has_hit = true;
-        }
-        
-    }
-
-    has_hit
-}
-
-fn new_point(player: &Player, _asteroids: &Vec<Asteroid>) -> Point2{
-    let mut valid_position = false;
-
-    let mut new_x = 0.0;
-    let mut new_y = 0.0;
-
-    while !valid_position{
-        let mut valid_spaceship_pos = false;
-        while !valid_spaceship_pos{
-            new_x = random_range((WINDOW_SIZE.0 as f32 / -2.0) + ASTEROID_MAX_SIZE, (WINDOW_SIZE.0 as f32 / 2.0) - ASTEROID_MAX_SIZE);
-            new_y = random_range((WINDOW_SIZE.1 as f32 / -2.0) + ASTEROID_MAX_SIZE, (WINDOW_SIZE.1 as f32 / 2.0) - ASTEROID_MAX_SIZE);
-        
-            let left_edge:bool = new_x < player.position.x - SPACESHIP_WIDTH;
-            let right_edge:bool = new_x > player.position.x + SPACESHIP_WIDTH;
-            let top_edge:bool = new_y > player.position.y + SPACESHIP_HEIGHT;
-            let bottom_edge:bool = new_y < player.position.y - SPACESHIP_HEIGHT;
-            if left_edge || right_edge
-            {
-                if top_edge || bottom_edge {
-                    valid_spaceship_pos = true;
-                    valid_position = true;
+fn idle_event(_app: &App, model: &mut Model, event: WindowEvent)
+{
+    if model.last_event != event
+    {
+        match event {
+            KeyPressed(key) => {
+                log::trace!(target: logging::INPUT, "Key Pressed");
+                if let Some((player, action)) = model.bindings.key(key){
+                    handle_command(model, player, Command::Pressed(action));
+                }
+            }
+            KeyReleased(key) => {
+                log::trace!(target: logging::INPUT, "Key Released");
+                if let Some((player, action)) = model.bindings.key(key){
+                    handle_command(model, player, Command::Released(action));
                 }
             }
+            _ => {}
         }
+        model.last_event = event;
     }
-
-    pt2(new_x, new_y)
 }
 
-fn generate_asteroid(position: Point2, num_points: u32, min_size: f32, max_size: f32, speed: f32, fragment: bool) -> Asteroid{
-    let new_size = random_range(min_size, max_size);
-    
-    let new_speed = random_range(ASTEROID_MIN_SPEED, ASTEROID_MAX_SPEED);
-
-    let mut thickness = ASTEROID_THICKNESS;
-    if fragment{
-        thickness *= 0.4;
-    }
-    let rotation = random_range(0.0, std::f32::consts::PI * 2.0);
-    let mut asteroid = Asteroid{
-        position: position,
-        rotation: 0.0,
-        rotation_speed: deg_to_rad(new_speed),
-        size: new_size,
-        points: Vec::new(),
-        num_points: num_points,
-        thickness: thickness,
-        thrust_rotation: rotation,
-        fragment: fragment,
-        speed: speed,
+/* Watching a replay, the keys drive the tape rather than the ships */
+fn replay_event(model: &mut Model, event: WindowEvent)
+{
+    let playback = match &mut model.playback{
+        Some(playback) => playback,
+        None => return,
     };
-    let angle_inc:f32 = (360 / asteroid.num_points) as f32;
-    for i in 0..asteroid.num_points{
-        let angle = deg_to_rad(i as f32 * angle_inc);
-        let radius = asteroid.size / 2.0;
 
-        let real_radius = random_range( radius * 0.80, radius * 1.20);
+    match event{
+        KeyPressed(Key::Space) => { playback.paused ^= true }
+        KeyPressed(Key::Right) if playback.paused => { playback.step = true }
+        KeyPressed(Key::Up) => { playback.faster() }
+        KeyPressed(Key::Down) => { playback.slower() }
+        _ => {}
+    }
+}
+
 
-        let x = angle.sin() * real_radius;
-        let y = angle.cos() * real_radius;
-        
-        asteroid.points.push(pt2(x,y));
+/* A sound that won't open is skipped, the game carries on without it.
+ * Weapon sounds were already warned about at startup. */
+fn play_sound(stream: &audio::Stream<Audio>, path: &str){
+    match audrey::open(path){
+        Ok(sound) => {stream.send( move |audio| {audio.event.push(sound)}).ok();}
+        Err(e) => log::debug!(target: logging::AUDIO, "Could not play {}: {}", path, e),
     }
+}
 
-    /* Connect the last dot */
-    asteroid.points.push(asteroid.points[0]);
-    asteroid
+/* A typo in weapons.toml only costs the sound, said once rather than on every shot */
+fn check_sounds(arsenal: &Arsenal){
+    for weapon in &arsenal.weapons{
+        if let Err(e) = audrey::open(&weapon.sound){
+            log::warn!(target: logging::AUDIO, "Weapon {} has sound {} that won't open: {}, it will be silent", weapon.name, weapon.sound, e);
+        }
+    }
 }
 
+
 fn update(app: &App, model: &mut Model, update: Update) { 
+    gamepad_update(app, model);
+    model.debug.record(update.since_last);
+    while let Some(message) = model.audio_log.pop(){
+        log::debug!(target: logging::AUDIO, "{}", message);
+    }
+
+    /* Nothing moves under the pause menu or its options, the last step stays on screen as it was */
+    if model.pause.is_some(){
+        return;
+    }
+
+    let mut since_last = update.since_last.min(MAX_FRAME_TIME);
+    if let Some(playback) = &mut model.playback{
+        let playing = matches!(model.game_state, State::Idle | State::Dying);
+        if playing && playback.paused{
+            /* Frame-step runs exactly one step and shows it as it landed */
+            if std::mem::take(&mut playback.step){
+                step(app, model);
+            }
+            model.alpha = 1.0;
+            return;
+        }
+        since_last = since_last.mul_f32(playback.speed());
+    }
+
+    /* The debug clock holds play still or runs it slow, the menus carry on as normal */
+    if matches!(model.game_state, State::Idle | State::Dying){
+        if model.debug.frozen{
+            if std::mem::take(&mut model.debug.step){
+                step(app, model);
+            }
+            model.alpha = 1.0;
+            return;
+        }
+        since_last = since_last.mul_f32(model.debug.scale);
+    }
+
+    model.accumulator += since_last;
+    while model.accumulator >= TIMESTEP{
+        model.accumulator -= TIMESTEP;
+        step(app, model);
+    }
+
+    /* Hold the last step still during hit-stop rather than blending back and forth */
+    model.alpha = if model.camera.frozen() { 1.0 } else { model.accumulator.as_secs_f32() / TIMESTEP.as_secs_f32() };
+}
+
+fn step(app: &App, model: &mut Model) {
+    /* Hit-stop can't hold up a networked game, the other end keeps flying */
+    if model.camera.update() && model.net.is_none(){
+        return;
+    }
+
     match model.game_state{
-        State::Idle => idle_update(app, model, update),
-        State::GameOver => gameover_update(app, model, update),
-        State::Menu => menu_update(app, model, update),
+        State::Idle => idle_update(app, model),
+        State::Dying => dying_update(app, model),
+        State::GameOver | State::Initials => gameover_update(app, model),
+        State::Menu => menu_update(app, model),
+        State::Paused => {}
+        /* Behind the options the title or score screen keeps ticking over */
+        State::Options => match model.options.as_ref().map(|options| options.from){
+            Some(State::Menu) => menu_update(app, model),
+            Some(State::GameOver) => gameover_update(app, model),
+            _ => {}
+        },
     }
 }
 
-fn gameover_update(_app: &App, _model: &mut Model, _update: Update) {
+
+fn gameover_update(_app: &App, model: &mut Model) {
+    model.input_lockout = model.input_lockout.saturating_sub(TIMESTEP);
+
+    /* Keep answering the other peers so they can confirm the last frames too */
+    if let Some(session) = &mut model.net{
+        session.pump(&mut model.world, Instant::now());
+        model.world.effects.clear();
+    }
 }
 
-fn menu_update(app: &App, model: &mut Model, _update: Update) {
-    let win = app.window_rect();
-    for asteroid in &mut model.asteroid{
+fn dying_update(_app: &App, model: &mut Model) {
+    play_step(model);
 
-        let true_rotation = asteroid.rotation + deg_to_rad(90.0); 
-        let asteroid_size = asteroid.size / 2.0;
+    /* A late input from another peer can take back the crash that ended it */
+    if !model.world.all_crashed(){
+        model.game_state = State::Idle;
+        return;
+    }
 
-        if asteroid.position.x + (asteroid_size * true_rotation.cos()) > (win.right()){
-            let new_pos_x = asteroid.position.x - WINDOW_SIZE.0 as f32;
-            asteroid.position.x = new_pos_x;
-        }
-        else if asteroid.position.x + (asteroid_size * true_rotation.cos()) < (win.left()){
-            let new_pos_x = asteroid.position.x + WINDOW_SIZE.0 as f32;
-            asteroid.position.x = new_pos_x;
+    /* Let the wreck fly apart before showing the score */
+    model.dying = model.dying.saturating_sub(TIMESTEP);
+    if model.dying.is_zero(){
+        /* Missiles still flying can score while the wreck breaks up, so save once it's done */
+        if let Some(mut recording) = model.recording.take(){
+            recording.finish(&model.world);
+            match recording.save(){
+                Ok(path) => log::info!("Saved replay {}", path.display()),
+                Err(error) => log::warn!("Could not save replay: {}", error),
+            }
         }
-        
-        if asteroid.position.y + (asteroid_size * true_rotation.sin()) > win.top(){
-            let new_pos_y = asteroid.position.y - WINDOW_SIZE.1 as f32;
-            asteroid.position.y = new_pos_y;
+
+        sign_scores(model);
+    }
+}
+
+/* The pilot flies the title screen, starting over a moment after it crashes */
+fn menu_update(_app: &App, model: &mut Model) {
+    model.menu_time += TIMESTEP;
+    let input = model.pilot.fly(&model.attract, 0);
+    world_step(&mut model.attract, &[input]);
+
+    model.particles.update();
+    for effect in std::mem::take(&mut model.attract.effects){
+        if let Effect::Crashed(_) = effect{
+            model.dying = Duration::from_secs_f32(particles::BREAKUP_LIFE);
         }
-        else if asteroid.position.y + (asteroid_size * true_rotation.sin()) < win.bottom(){
-            let new_pos_y = asteroid.position.y + WINDOW_SIZE.1 as f32;
-            asteroid.position.y = new_pos_y;
+        emit_effect(&mut model.particles, &model.attract, &effect);
+    }
+    emit_exhaust(&mut model.particles, &model.attract);
+
+    if model.attract.all_crashed(){
+        model.dying = model.dying.saturating_sub(TIMESTEP);
+        if model.dying.is_zero(){
+            let seed = model.attract.rng.next_u64() & 0xFFFF_FFFF;
+            model.attract.restart(1, seed);
+            model.pilot = Pilot::new();
         }
-        
-        asteroid.rotation += asteroid.rotation_speed;
-        asteroid.position.x += -ASTEROID_SPEED * asteroid.thrust_rotation.sin();
-        asteroid.position.y += ASTEROID_SPEED * asteroid.thrust_rotation.cos();
     }
-    
-    /* Generate new asteroid if needed */
-    if model.asteroid.len() < model.difficulty.max_asteroids as usize
-    {
-        let new_pt =  new_point(&model.player, &model.asteroid);
-        let asteroid = generate_asteroid(new_pt, 12, ASTEROID_MIN_SIZE, ASTEROID_MAX_SIZE, model.difficulty.asteroid_speed, false);
 
-        model.asteroid.push(asteroid);
-    }
-    
     let current_time:Instant = Instant::now();
     let duration = Duration::new(0, 500000000);
 
@@ -552,130 +931,143 @@ fn menu_update(app: &App, model: &mut Model, _update: Update) {
     }
 }
 
-fn idle_update(app: &App, model: &mut Model, _update: Update) {
-    let win = app.window_rect();
-    let current_tick:Instant = Instant::now();
+fn idle_update(_app: &App, model: &mut Model) {
+    play_step(model);
+
+    if model.world.all_crashed(){
+        model.game_state = State::Dying;
+        model.dying = Duration::from_secs_f32(particles::BREAKUP_LIFE);
 
-    if current_tick.duration_since(model.difficulty.tick) > model.difficulty.duration{
-        model.difficulty.tick = Instant::now();
-        model.difficulty.max_asteroids += 1;
-        model.difficulty.asteroid_speed += 0.25;
-        println!("Difficulty Increase!");
+        if let Some(recording) = &mut model.recording{
+            recording.ended = Some(model.world.frame);
+        }
     }
+}
 
-    /* First, has the model crashed into anything? */
-    let crashed = has_ship_hit_asteroid(&model.player, &model.asteroid);
+/* Moves the world on a step, through the session when networked, then plays
+ * back whatever the step left behind */
+fn play_step(model: &mut Model){
+    let advanced = match &mut model.net{
+        Some(session) => session.advance(&mut model.world, model.inputs[0], Instant::now()),
+        None => {
+            let inputs = match &model.playback{
+                Some(playback) => playback.replay.inputs(model.world.frame),
+                None => model.inputs.clone(),
+            };
+            if let Some(recording) = &mut model.recording{
+                recording.record(&inputs);
+            }
+            world_step(&mut model.world, &inputs);
+            true
+        }
+    };
 
-    if crashed{
-        model.game_state = State::GameOver;
+    /* A peer set up differently will never be played with, so the session's over */
+    if let Some(player) = model.net.as_ref().and_then(|session| session.refused){
+        model.notice = Some(format!("PEER REFUSED: SETUP MISMATCH WITH PLAYER {}", player + 1));
+        model.net = None;
+        quit_to_title(model);
+        return;
     }
 
-    model.player.rotation += model.player.rotation_inc;
-    if model.player.thrust{
-        model.player.thrust_rotation = model.player.rotation;
-        model.player.thrust_counter = 0;
+    /* Waiting on the other peers, hang on to any fire press for the next go */
+    if !advanced{
+        return;
     }
 
-    /* Handle wrapping across boundaries for space ship */
-    let true_rotation = model.player.rotation + deg_to_rad(90.0);
-    if model.player.position.x + (SPACESHIP_PEAK * true_rotation.cos()) > (win.right()){
-        let new_pos_x = model.player.position.x - WINDOW_SIZE.0 as f32;
-        model.player.position.x = new_pos_x;
-    }
-    else if model.player.position.x + (SPACESHIP_PEAK * true_rotation.cos()) < (win.left()){
-        let new_pos_x = model.player.position.x + WINDOW_SIZE.0 as f32;
-        model.player.position.x = new_pos_x;
+    for input in &mut model.inputs{
+        input.fire = false;
+        input.switch = false;
     }
 
-    if model.player.position.y + (SPACESHIP_PEAK * true_rotation.sin()) > win.top(){
-        let new_pos_y = model.player.position.y - WINDOW_SIZE.1 as f32;
-        model.player.position.y = new_pos_y;
-    }
-    else if model.player.position.y + (SPACESHIP_PEAK * true_rotation.sin()) < win.bottom(){
-        let new_pos_y = model.player.position.y + WINDOW_SIZE.1 as f32;
-        model.player.position.y = new_pos_y;
-    }
+    model.particles.update();
+    play_effects(model);
+}
 
-    //let exp = (model.player.thrust_counter as f32 * -0.012).exp();
-    let exp = (model.player.thrust_counter as f32 * -0.5).exp() + 0.2;
-    model.player.position.x += -SPACESHIP_SPEED * model.player.thrust_rotation.sin() * exp;
-    model.player.position.y += SPACESHIP_SPEED * model.player.thrust_rotation.cos() * exp;
-    if model.player.thrust_counter < u32::MAX{
-        model.player.thrust_counter += 1;
+/* Particles for one effect, the same in play and on the title screen */
+fn emit_effect(particles: &mut Particles, world: &World, effect: &Effect){
+    match effect{
+        Effect::Fired(_) => {}
+        Effect::Spark(position) => particles.emit_sparks(*position),
+        Effect::Destroyed(asteroid) | Effect::Cracked(asteroid) => particles.emit_shatter(asteroid),
+        Effect::Bombed(asteroids) => asteroids.iter().for_each(|asteroid| particles.emit_shatter(asteroid)),
+        Effect::Crashed(index) => particles.emit_breakup(&world.players[*index], &ship_hull()),
+        Effect::Collected(_, position) => particles.emit_sparks(*position),
     }
+}
 
-    for asteroid in &mut model.asteroid{
-
-        let true_rotation = asteroid.rotation + deg_to_rad(90.0); 
-        let asteroid_size = asteroid.size / 2.0;
+fn emit_exhaust(particles: &mut Particles, world: &World){
+    for player in world.players.iter().filter(|player| player.alive && player.thrust){
+        particles.emit_exhaust(player);
+    }
+}
 
-        if asteroid.position.x + (asteroid_size * true_rotation.cos()) > (win.right()){
-            let new_pos_x = asteroid.position.x - WINDOW_SIZE.0 as f32;
-            asteroid.position.x = new_pos_x;
-        }
-        else if asteroid.position.x + (asteroid_size * true_rotation.cos()) < (win.left()){
-            let new_pos_x = asteroid.position.x + WINDOW_SIZE.0 as f32;
-            asteroid.position.x = new_pos_x;
-        }
-        
-        if asteroid.position.y + (asteroid_size * true_rotation.sin()) > win.top(){
-            let new_pos_y = asteroid.position.y - WINDOW_SIZE.1 as f32;
-            asteroid.position.y = new_pos_y;
-        }
-        else if asteroid.position.y + (asteroid_size * true_rotation.sin()) < win.bottom(){
-            let new_pos_y = asteroid.position.y + WINDOW_SIZE.1 as f32;
-            asteroid.position.y = new_pos_y;
+fn play_effects(model: &mut Model){
+    for effect in std::mem::take(&mut model.world.effects){
+        emit_effect(&mut model.particles, &model.world, &effect);
+        match effect{
+            Effect::Fired(weapon) => play_sound(&model.stream, &model.world.arsenal.weapons[weapon].sound),
+            Effect::Spark(_) => {}
+            Effect::Destroyed(asteroid) => {
+                model.camera.asteroid_destroyed(asteroid.size, asteroid.fragment);
+                play_sound(&model.stream, "assets/space_clutter_boom.wav");
+            }
+            Effect::Cracked(asteroid) => model.camera.asteroid_cracked(asteroid.size),
+            Effect::Crashed(_) => {
+                model.camera.ship_crashed();
+                play_sound(&model.stream, "assets/space_clutter_boom.wav");
+            }
+            Effect::Bombed(_) => {
+                model.camera.smart_bomb();
+                play_sound(&model.stream, "assets/space_clutter_boom.wav");
+            }
+            Effect::Collected(..) => {}
         }
-        
-        asteroid.rotation += asteroid.rotation_speed;
-        asteroid.position.x += -asteroid.speed * asteroid.thrust_rotation.sin();
-        asteroid.position.y += asteroid.speed * asteroid.thrust_rotation.cos();
     }
-    
-    let mut fragments:Vec<Asteroid> = Vec::new();
-    assert_eq!(fragments.len(), 0);
-    model.asteroid.retain(|asteroids| !has_missile_hit_asteroid(&mut model.player.missile, asteroids, &mut model.player.score, &mut fragments, &mut model.stream));
 
-    for asteroid in fragments{
-        model.asteroid.push(asteroid);
-    }
+    emit_exhaust(&mut model.particles, &model.world);
+}
 
-    model.player.missile.retain(|missiles| !has_missile_hit_edge(missiles, app.window_rect()));
-    
-    for missile in &mut model.player.missile{
-        missile.position.x += -MISSILE_SPEED * missile.rotation.sin();
-        missile.position.y += MISSILE_SPEED * missile.rotation.cos();
-    }
 
-    /* Generate new asteroid if needed */
-    if model.asteroid.len() < model.difficulty.max_asteroids as usize
-    {
-        let new_pt =  new_point(&model.player, &model.asteroid);
-        let asteroid = generate_asteroid(new_pt, 8, ASTEROID_MIN_SIZE, ASTEROID_MAX_SIZE, model.difficulty.asteroid_speed, false);
+/* Keys, buttons and sticks only change what each ship's controls are holding,
+ * the world picks them up on its next step */
+fn handle_command(model: &mut Model, player: usize, command: Command)
+{
+    let input = match model.inputs.get_mut(player){
+        Some(input) => input,
+        None => return,
+    };
 
-        model.asteroid.push(asteroid);
+    match command{
+        Command::Turn(turn) => input.turn = (turn * i8::MAX as f32).round() as i8,
+        _ => input.apply(command.state()),
     }
 }
 
-fn handle_event(model: &mut Model, event:StateEvents)
-{
-    match event{
-        StateEvents::LeftKeyPress =>{model.player.rotation_inc = deg_to_rad(ANGLE_INC)},
-        StateEvents::LeftKeyRelease => {model.player.rotation_inc = deg_to_rad(0.0)},
-        StateEvents::RightKeyPress => {model.player.rotation_inc = deg_to_rad(-ANGLE_INC)},
-        StateEvents::RightKeyRelease => {model.player.rotation_inc = deg_to_rad(0.0)},
-        StateEvents::UpKeyPress => {model.player.thrust = true},
-        StateEvents::UpKeyRelease => {model.player.thrust = false},
-        StateEvents::SpaceKeyPress => { fire_missile(model) },
-        _ => { /* Do nowt */}
+/* Pads drive the ships in play and start a game from the menus like any key would */
+fn gamepad_update(app: &App, model: &mut Model){
+    for PadEvent{ player, command, nav } in model.gamepads.poll(&model.bindings){
+        /* In play the pad's bindings count, in the menus its D-pad and stick do */
+        match (model.game_state, command, nav){
+            (State::Idle | State::Dying, Some(command), _) if matches!(command.state(), StateEvents::PauseKeyPress) => pause(model),
+            (State::Idle, Some(command), _) => handle_command(model, player, command),
+            (State::Paused, _, Some(nav)) => pause_input(app, model, nav),
+            (State::Options, _, Some(nav)) => options_input(model, nav),
+            (State::Menu, _, Some(Nav::Select)) => reset(app, model),
+            (State::GameOver, _, Some(Nav::Select)) if model.input_lockout.is_zero() && model.net.is_none() => reset(app, model),
+            (State::Initials, _, Some(nav)) if model.input_lockout.is_zero() => initials_input(model, nav),
+            _ => {}
+        }
     }
 }
 
 fn view(app: &App, model: &Model, frame: Frame){
     match model.game_state{
-        State::Idle => idle_view(app, model, frame),
+        State::Idle | State::Dying | State::Paused => idle_view(app, model, frame),
+        State::Options => options_view(app, model, frame),
         State::GameOver => gameover_view(app, model, frame),
         State::Menu => menu_view(app, model, frame),
+        State::Initials => initials_view(app, model, frame),
     }
 }
 
@@ -696,20 +1088,67 @@ fn gameover_view(app: &App, model: &Model, frame: Frame){
     /* Draw score */
     let score_font: Font = Font::from_bytes(model.score_font.clone()).unwrap();
     
-    let score = format!("Score: {}", model.player.score);
-    draw.text(&score)
-        .font(score_font)
-        .no_line_wrap()
-        .font_size(60)
-        .xy(pt2(0.0 , win.top() - 150.0));
+    let count = model.world.players.len();
+    if count > 1{
+        for (i, player) in model.world.players.iter().enumerate(){
+            let score = format!("P{}: {}", i + 1, player.score);
+            let spacing = 480.0 / count as f32;
+            let x = (i as f32 - (count - 1) as f32 / 2.0) * spacing;
+            draw.text(&score)
+                .font(score_font.clone())
+                .no_line_wrap()
+                .font_size(50)
+                .xy(pt2(x , win.top() - 150.0));
+        }
+    }
+    else{
+        let score = format!("Score: {}", model.world.players[0].score);
+        draw.text(&score)
+            .font(score_font)
+            .no_line_wrap()
+            .font_size(60)
+            .xy(pt2(0.0 , win.top() - 150.0));
+    }
+
+    if model.world.mode == GameMode::Waves{
+        let score_font: Font = Font::from_bytes(model.score_font.clone()).unwrap();
+        let wave = format!("Wave: {}", model.world.wave.number);
+        draw.text(&wave)
+            .font(score_font)
+            .no_line_wrap()
+            .font_size(40)
+            .xy(pt2(0.0 , win.top() - 200.0));
+    }
     
     let credit_font: Font = Font::from_bytes(model.credit_font.clone()).unwrap();
-    let anykey = format!("press any key to retry");
-    draw.text(&anykey)
+    /* Enough to replay the same field with --seed */
+    let seed = format!("seed {}", model.world.seed);
+    draw.text(&seed)
         .font(credit_font.clone())
         .no_line_wrap()
-        .font_size(20)
-        .xy(pt2(0.0, win.top() -250.0));
+        .font_size(16)
+        .xy(pt2(0.0, win.top() - 285.0));
+
+    if !model.placed.is_empty(){
+        let placed = model.placed.iter()
+            .map(|&(player, place)| if count > 1 { format!("P{} #{}", player + 1, place + 1) } else { format!("#{}", place + 1) })
+            .collect::<Vec<String>>()
+            .join("  ");
+        draw.text(&format!("HIGH SCORE {}", placed))
+            .font(credit_font.clone())
+            .no_line_wrap()
+            .font_size(16)
+            .xy(pt2(0.0, win.top() - 225.0));
+    }
+
+    if model.net.is_none(){
+        let anykey = format!("press any key to retry");
+        draw.text(&anykey)
+            .font(credit_font.clone())
+            .no_line_wrap()
+            .font_size(20)
+            .xy(pt2(0.0, win.top() -250.0));
+    }
 
 
     let credits = format!("Coding + Music by T.L. '23");
@@ -728,67 +1167,117 @@ fn gameover_view(app: &App, model: &Model, frame: Frame){
     draw.to_frame(app, &frame).unwrap();
 }
 
-fn menu_view(app: &App, model: &Model, frame: Frame){
+fn draw_high_scores(draw: &Draw, win: Rect, model: &Model){
+    let actual_font: Font = Font::from_bytes(model.raw_font.clone()).unwrap();
+    draw.text("HIGH SCORES")
+        .font(actual_font)
+        .no_line_wrap()
+        .font_size(30)
+        .xy(pt2(0.0, win.top() - 40.0));
+
+    let credit_font: Font = Font::from_bytes(model.credit_font.clone()).unwrap();
+    for (place, entry) in model.scores.entries.iter().enumerate(){
+        let row = format!("{:>2}. {}  {:>6}  {:<7} {:<6}", place + 1, entry.initials, entry.score, entry.mode, entry.preset);
+        draw.text(&row)
+            .font(credit_font.clone())
+            .no_line_wrap()
+            .font_size(16)
+            .xy(pt2(0.0, win.top() - 80.0 - place as f32 * 18.0));
+    }
+}
+
+/* The letters so far with a bar under the one being picked */
+fn initials_view(app: &App, model: &Model, frame: Frame){
     let win = app.window_rect();
     let draw = app.draw();
     draw.background().color(BLACK);
-    
-    for asteroid in &model.asteroid{ 
-        draw.polyline()
-            .xy(asteroid.position)
-            .weight(asteroid.thickness)
-            .color(WHITE)
-            .rotate(asteroid.rotation)
-            .points(asteroid.points.clone());
-        
-        if asteroid.position.x + (asteroid.size) >= (win.right()){
-            let new_pos_x = asteroid.position.x - WINDOW_SIZE.0 as f32;
-            draw.polyline()
-                .x_y(new_pos_x, asteroid.position.y)
-                .weight(asteroid.thickness)
-                .color(WHITE)
-                .rotate(asteroid.rotation)
-                .points(asteroid.points.clone());
-        }
-        else if asteroid.position.x - (asteroid.size) <= (win.left()){
-            let new_pos_x = asteroid.position.x + WINDOW_SIZE.0 as f32;
-            draw.polyline()
-                .x_y(new_pos_x, asteroid.position.y)
-                .weight(asteroid.thickness)
-                .color(WHITE)
-                .rotate(asteroid.rotation)
-                .points(asteroid.points.clone());
-        }
-        
-        if asteroid.position.y + (asteroid.size) >= (win.top()){
-            let new_pos_y = asteroid.position.y - WINDOW_SIZE.1 as f32;
-            draw.polyline()
-                .x_y(asteroid.position.x, new_pos_y)
-                .weight(asteroid.thickness)
-                .color(WHITE)
-                .rotate(asteroid.rotation)
-                .points(asteroid.points.clone());
-        }
-        else if asteroid.position.y - (asteroid.size) <= (win.bottom()){
-            let new_pos_y = asteroid.position.y + WINDOW_SIZE.1 as f32;
-            draw.polyline()
-                .x_y(asteroid.position.x, new_pos_y)
-                .weight(asteroid.thickness)
-                .color(WHITE)
-                .rotate(asteroid.rotation)
-                .points(asteroid.points.clone());
-        }
-    }
-
 
+    let initials = match &model.initials{
+        Some(initials) => initials,
+        None => return,
+    };
+    let player = initials.players[0];
 
     let actual_font: Font = Font::from_bytes(model.raw_font.clone()).unwrap();
-    let title = format!("SPACE CLUTTER");
-    draw.text(&title)
+    draw.text("NEW HIGH SCORE")
         .font(actual_font)
         .no_line_wrap()
-        .font_size(60)
-        .xy(pt2(0.0, win.top() - 100.0));    
+        .font_size(45)
+        .xy(pt2(0.0, win.top() - 75.0));
+
+    let score_font: Font = Font::from_bytes(model.score_font.clone()).unwrap();
+    let score = if model.world.players.len() > 1{
+        format!("P{}: {}", player + 1, model.world.players[player].score)
+    }
+    else{
+        format!("Score: {}", model.world.players[player].score)
+    };
+    draw.text(&score)
+        .font(score_font.clone())
+        .no_line_wrap()
+        .font_size(50)
+        .xy(pt2(0.0, win.top() - 150.0));
+
+    let spacing = 50.0;
+    for (i, &letter) in initials.letters.iter().enumerate(){
+        let x = (i as f32 - (scores::INITIALS_LENGTH - 1) as f32 / 2.0) * spacing;
+        draw.text(&(INITIALS_ALPHABET[letter] as char).to_string())
+            .font(score_font.clone())
+            .no_line_wrap()
+            .font_size(60)
+            .xy(pt2(x, 0.0));
+        if i == initials.cursor{
+            draw.line()
+                .start(pt2(x - 15.0, -30.0))
+                .end(pt2(x + 15.0, -30.0))
+                .weight(3.0)
+                .color(WHITE);
+        }
+    }
+
+    let credit_font: Font = Font::from_bytes(model.credit_font.clone()).unwrap();
+    draw.text("up / down to pick a letter, space for the next")
+        .font(credit_font)
+        .no_line_wrap()
+        .font_size(16)
+        .xy(pt2(0.0, win.bottom() + 100.0));
+    draw.to_frame(app, &frame).unwrap();
+}
+
+fn menu_view(app: &App, model: &Model, frame: Frame){
+    let win = app.window_rect();
+    let draw = app.draw();
+    draw.background().color(BLACK);
+    
+    let pickup_font: Font = Font::from_bytes(model.credit_font.clone()).unwrap();
+    let view = view_of(&model.attract, 0, model.alpha, win);
+    draw_world(&draw, &view, &model.attract, model.alpha, &pickup_font);
+    particles::draw_particles(&draw, &view, &model.particles, model.alpha);
+
+    /* Every other page shows the table, once there is one to show */
+    let page = model.menu_time.as_secs() / MENU_PAGE_TIME.as_secs();
+    if page % 2 == 1 && !model.scores.entries.is_empty(){
+        draw_high_scores(&draw, win, model);
+    }
+    else{
+        let actual_font: Font = Font::from_bytes(model.raw_font.clone()).unwrap();
+        let title = format!("SPACE CLUTTER");
+        draw.text(&title)
+            .font(actual_font)
+            .no_line_wrap()
+            .font_size(60)
+            .xy(pt2(0.0, win.top() - 100.0));    
+    }
+
+    if let Some(notice) = &model.notice{
+        let credit_font: Font = Font::from_bytes(model.credit_font.clone()).unwrap();
+        draw.text(notice)
+            .font(credit_font)
+            .no_line_wrap()
+            .font_size(16)
+            .color(RED)
+            .xy(pt2(0.0, -70.0));
+    }
 
     if model.display_text{
         let credit_font: Font = Font::from_bytes(model.credit_font.clone()).unwrap();
@@ -799,133 +1288,413 @@ fn menu_view(app: &App, model: &Model, frame: Frame){
             .font_size(20)
             .xy(pt2(0.0, -100.0));
     }
+
+    let credit_font: Font = Font::from_bytes(model.credit_font.clone()).unwrap();
+    let preset = format!("< {} >", model.world.difficulty.preset.name());
+    draw.text(&preset)
+        .font(credit_font)
+        .no_line_wrap()
+        .font_size(20)
+        .xy(pt2(0.0, -150.0));
+
+    let credit_font: Font = Font::from_bytes(model.credit_font.clone()).unwrap();
+    let mode = format!("^ {} v", model.world.mode.name());
+    draw.text(&mode)
+        .font(credit_font)
+        .no_line_wrap()
+        .font_size(20)
+        .xy(pt2(0.0, -180.0));
+
+    let credit_font: Font = Font::from_bytes(model.credit_font.clone()).unwrap();
+    let players = if model.coop { "[2] CO-OP" } else { "[2] SOLO" };
+    let friendly_fire = if model.world.friendly_fire { "[F] FRIENDLY FIRE ON" } else { "[F] FRIENDLY FIRE OFF" };
+    let options = if model.coop { format!("{}   {}", players, friendly_fire) } else { players.to_string() };
+    draw.text(&options)
+        .font(credit_font)
+        .no_line_wrap()
+        .font_size(16)
+        .xy(pt2(0.0, -210.0));
+
+    let credit_font: Font = Font::from_bytes(model.credit_font.clone()).unwrap();
+    let world = if model.world.large { "[L] LARGE WORLD, SOLO ONLY" } else { "[L] ONE SCREEN" };
+    draw.text(&format!("{}   [O] OPTIONS", world))
+        .font(credit_font)
+        .no_line_wrap()
+        .font_size(16)
+        .xy(pt2(0.0, -230.0));
     draw.to_frame(app, &frame).unwrap();
 }
 
-fn idle_view(app: &App, model: &Model, frame: Frame){
+fn options_view(app: &App, model: &Model, frame: Frame){
     let win = app.window_rect();
     let draw = app.draw();
     draw.background().color(BLACK);
-    
 
-    if model.player.thrust{
-        let point5 = pt2(-7.0,-10.0);
-        let point6 = pt2(7.0,-10.0);
-        let point7 = pt2(0.0,-40.0);
-        draw.tri()
-            .points(point5,point6,point7)
-            .x_y(model.player.position.x, model.player.position.y)
-            .rotate(model.player.rotation)
-            .color(WHITE);
-    }
-    let point1 = pt2(-(SPACESHIP_WIDTH / 2.0), -(SPACESHIP_PEAK + SPACESHIP_TROUGH));
-    let point2 = pt2(0.0, -SPACESHIP_PEAK);
-    let point3 = pt2(SPACESHIP_WIDTH / 2.0, -(SPACESHIP_PEAK + SPACESHIP_TROUGH));
-    let point4 = pt2(0.0, SPACESHIP_PEAK);
+    let options = match &model.options{
+        Some(options) => options,
+        None => return,
+    };
 
-    draw.quad()
-        .points(point1,point2,point3,point4)
-        .x_y(model.player.position.x, model.player.position.y)
-        .rotate(model.player.rotation)
-        .color(WHITE);
+    let actual_font: Font = Font::from_bytes(model.raw_font.clone()).unwrap();
+    draw.text("OPTIONS")
+        .font(actual_font)
+        .no_line_wrap()
+        .font_size(40)
+        .xy(pt2(0.0, win.top() - 50.0));
 
-    let true_rotation = model.player.rotation + deg_to_rad(90.0 + 180.0);
-    if model.player.position.x + (SPACESHIP_PEAK * true_rotation.cos()) > (win.right()){
-        let new_pos_x = model.player.position.x - WINDOW_SIZE.0 as f32;
-        draw.quad()
-            .points(point1,point2,point3,point4)
-            .x_y(new_pos_x, model.player.position.y)
-            .rotate(model.player.rotation)
-            .color(WHITE);
+    let credit_font: Font = Font::from_bytes(model.credit_font.clone()).unwrap();
+    let rows: Vec<menu::Row> = model.settings.items(&model.bindings).into_iter().map(|item| model.settings.row(&model.bindings, item)).collect();
+    menu::draw_rows(&draw, &credit_font, &rows, &options.cursor, win.top() - 110.0);
+
+    if model.pause.is_some(){
+        draw.text("difficulty changes start with the next game")
+            .font(credit_font.clone())
+            .no_line_wrap()
+            .font_size(14)
+            .xy(pt2(0.0, win.bottom() + 55.0));
     }
-    else if model.player.position.x + (SPACESHIP_PEAK * true_rotation.cos()) < (win.left()){
-        let new_pos_x = model.player.position.x + WINDOW_SIZE.0 as f32;
-        draw.quad()
-            .points(point1,point2,point3,point4)
-            .x_y(new_pos_x, model.player.position.y)
-            .rotate(model.player.rotation)
-            .color(WHITE);
+    draw.text("up / down to pick, left / right to change, esc to go back")
+        .font(credit_font)
+        .no_line_wrap()
+        .font_size(14)
+        .xy(pt2(0.0, win.bottom() + 30.0));
+    draw.to_frame(app, &frame).unwrap();
+}
+
+fn draw_player(draw: &Draw, view: &View, player: &Player, alpha: f32){
+    if (player.invulnerable.as_millis() / INVULNERABLE_BLINK_MS) % 2 == 1{
+        return;
     }
-    
-    if model.player.position.y + (SPACESHIP_PEAK * true_rotation.sin()) > (win.top()){
-        let new_pos_y = model.player.position.y - WINDOW_SIZE.1 as f32;
+
+    let player_position = interpolate(player.previous_position, player.position, alpha);
+    let player_rotation = player.previous_rotation + (player.rotation - player.previous_rotation) * alpha;
+    let [point1, point2, point3, point4] = ship_hull();
+
+    for position in view.images(player_position, SPACESHIP_HEIGHT){
+        if player.thrust{
+            let point5 = pt2(-7.0,-10.0);
+            let point6 = pt2(7.0,-10.0);
+            let point7 = pt2(0.0,-40.0);
+            draw.tri()
+                .points(point5,point6,point7)
+                .x_y(position.x, position.y)
+                .rotate(player_rotation)
+                .color(WHITE);
+        }
+
         draw.quad()
             .points(point1,point2,point3,point4)
-            .x_y(model.player.position.x, new_pos_y)
-            .rotate(model.player.rotation)
+            .x_y(position.x, position.y)
+            .rotate(player_rotation)
             .color(WHITE);
     }
-    else if model.player.position.y + (SPACESHIP_PEAK * true_rotation.sin()) < (win.bottom()){
-        let new_pos_y = model.player.position.y + WINDOW_SIZE.1 as f32;
-        draw.quad()
-            .points(point1,point2,point3,point4)
-            .x_y(model.player.position.x, new_pos_y)
-            .rotate(model.player.rotation)
-            .color(WHITE);
+}
+
+fn blinking(left: Duration, warning: Duration) -> bool{
+    left < warning && (left.as_millis() / INVULNERABLE_BLINK_MS) % 2 == 1
+}
+
+/* Rings shrinking in towards the horizon, brightening as they go */
+fn draw_well(draw: &Draw, position: Point2, horizon: f32, frame: u32, alpha: f32){
+    let time = (frame as f32 + alpha) / WELL_RING_STEPS;
+    for ring in 0..WELL_RINGS{
+        let fall = (time + ring as f32 / WELL_RINGS as f32).fract();
+        draw.ellipse()
+            .xy(position)
+            .radius(WELL_RING_REACH - (WELL_RING_REACH - horizon) * fall)
+            .no_fill()
+            .stroke(rgba(1.0, 1.0, 1.0, fall))
+            .stroke_weight(1.0);
     }
 
-    for missile in &model.player.missile{
-        draw.rect()
-            .xy(missile.position)
-            .w_h(MISSILE_SIZE, MISSILE_SIZE)
-            .color(WHITE);
+    draw.ellipse()
+        .xy(position)
+        .radius(horizon)
+        .color(BLACK)
+        .stroke(WHITE)
+        .stroke_weight(2.0);
+}
+
+/* Wells, ships, shots, rocks and pickups, wrapping round the edges */
+fn draw_world(draw: &Draw, view: &View, world: &World, alpha: f32, font: &Font){
+    for well in &world.wells{
+        for position in view.images(well.position, WELL_RING_REACH){
+            draw_well(draw, position, world.hazards.horizon, world.frame, alpha);
+        }
     }
 
-    for asteroid in &model.asteroid{ 
-        draw.polyline()
-            .xy(asteroid.position)
-            .weight(asteroid.thickness)
-            .color(WHITE)
-            .rotate(asteroid.rotation)
-            .points(asteroid.points.clone());
-        
-        if asteroid.position.x + (asteroid.size) >= (win.right()){
-            let new_pos_x = asteroid.position.x - WINDOW_SIZE.0 as f32;
-            draw.polyline()
-                .x_y(new_pos_x, asteroid.position.y)
-                .weight(asteroid.thickness)
-                .color(WHITE)
-                .rotate(asteroid.rotation)
-                .points(asteroid.points.clone());
+    for player in &world.players{
+        if player.alive{
+            draw_player(draw, view, player, alpha);
+
+            if !player.powers.shield.is_zero() && !blinking(player.powers.shield, SHIELD_WARNING){
+                for position in view.images(interpolate(player.previous_position, player.position, alpha), SPACESHIP_HEIGHT){
+                    draw.ellipse()
+                        .xy(position)
+                        .radius(SPACESHIP_HEIGHT * 0.7)
+                        .no_fill()
+                        .stroke(WHITE)
+                        .stroke_weight(1.5);
+                }
+            }
         }
-        else if asteroid.position.x - (asteroid.size) <= (win.left()){
-            let new_pos_x = asteroid.position.x + WINDOW_SIZE.0 as f32;
-            draw.polyline()
-                .x_y(new_pos_x, asteroid.position.y)
-                .weight(asteroid.thickness)
-                .color(WHITE)
-                .rotate(asteroid.rotation)
-                .points(asteroid.points.clone());
+
+        for missile in &player.missile{
+            let position = view.place(interpolate(missile.previous_position, missile.position, alpha));
+            match world.arsenal.weapons[missile.weapon].shape{
+                Shape::Square => {
+                    draw.rect()
+                        .xy(position)
+                        .w_h(missile.size, missile.size)
+                        .color(WHITE);
+                }
+                Shape::Streak => {
+                    /* Trails back along the shot the distance it covers in a step */
+                    draw.line()
+                        .start(position)
+                        .end(position - missile.velocity)
+                        .weight(missile.size / 2.0)
+                        .color(WHITE);
+                }
+                Shape::Dart => {
+                    draw.tri()
+                        .points(pt2(0.0, missile.size / 2.0), pt2(-missile.size / 4.0, -missile.size / 2.0), pt2(missile.size / 4.0, -missile.size / 2.0))
+                        .xy(position)
+                        .rotate(missile.rotation)
+                        .color(WHITE);
+                }
+                Shape::Ring => {
+                    draw.ellipse()
+                        .xy(position)
+                        .radius(missile.size / 2.0)
+                        .no_fill()
+                        .stroke(WHITE)
+                        .stroke_weight(1.5);
+                }
+            }
         }
-        
-        if asteroid.position.y + (asteroid.size) >= (win.top()){
-            let new_pos_y = asteroid.position.y - WINDOW_SIZE.1 as f32;
-            draw.polyline()
-                .x_y(asteroid.position.x, new_pos_y)
-                .weight(asteroid.thickness)
-                .color(WHITE)
-                .rotate(asteroid.rotation)
-                .points(asteroid.points.clone());
+    }
+
+    for pickup in world.pickups.iter().filter(|pickup| !blinking(pickup.life, PICKUP_WARNING)){
+        for position in view.images(interpolate(pickup.previous_position, pickup.position, alpha), PICKUP_RADIUS){
+            draw.ellipse()
+                .xy(position)
+                .radius(PICKUP_RADIUS)
+                .no_fill()
+                .stroke(WHITE)
+                .stroke_weight(2.0);
+            draw.text(&pickup.power.name()[..1])
+                .font(font.clone())
+                .font_size(12)
+                .xy(position);
         }
-        else if asteroid.position.y - (asteroid.size) <= (win.bottom()){
-            let new_pos_y = asteroid.position.y + WINDOW_SIZE.1 as f32;
+    }
+
+    for asteroid in &world.asteroid{ 
+        let rotation = asteroid.previous_rotation + (asteroid.rotation - asteroid.previous_rotation) * alpha;
+        for position in view.images(interpolate(asteroid.previous_position, asteroid.position, alpha), asteroid.size){
             draw.polyline()
-                .x_y(asteroid.position.x, new_pos_y)
+                .xy(position)
                 .weight(asteroid.thickness)
                 .color(WHITE)
-                .rotate(asteroid.rotation)
+                .rotate(rotation)
                 .points(asteroid.points.clone());
         }
     }
+}
+
+/* The whole world shrunk into a corner, the box marks what the window shows */
+fn draw_radar(draw: &Draw, view: &View, world: &World, top_right: Point2){
+    let scale = RADAR_WIDTH / world.field.w();
+    let size = vec2(RADAR_WIDTH, world.field.h() * scale);
+    let radar = Rect::from_xy_wh(top_right - size / 2.0, size);
+    /* Drawn round the followed ship, the same as the main view */
+    let plot = |position: Point2| radar.xy() + view.place(position) * scale;
+
+    draw.rect()
+        .xy(radar.xy())
+        .wh(radar.wh())
+        .color(BLACK)
+        .stroke(WHITE)
+        .stroke_weight(1.0);
+    draw.rect()
+        .xy(radar.xy())
+        .wh(view.screen.wh() * scale)
+        .no_fill()
+        .stroke(rgba(1.0, 1.0, 1.0, 0.4))
+        .stroke_weight(1.0);
+
+    for well in &world.wells{
+        draw.ellipse().xy(plot(well.position)).radius(3.0).no_fill().stroke(WHITE).stroke_weight(1.0);
+    }
+    for asteroid in &world.asteroid{
+        draw.ellipse().xy(plot(asteroid.position)).radius((asteroid.size * scale / 2.0).max(1.0)).color(GRAY);
+    }
+    for pickup in &world.pickups{
+        draw.rect().xy(plot(pickup.position)).w_h(3.0, 3.0).color(WHITE);
+    }
+    for player in world.players.iter().filter(|player| player.alive){
+        draw.tri()
+            .points(pt2(0.0, 4.0), pt2(-3.0, -3.0), pt2(3.0, -3.0))
+            .xy(plot(player.position))
+            .rotate(player.rotation)
+            .color(WHITE);
+    }
+}
+
+/* The weapon in hand, then seconds left on each running power, rounded up so
+ * nothing reads 0 while still on */
+fn power_timers(player: &Player, arsenal: &Arsenal) -> String{
+    let powers = player.powers.active()
+        .into_iter()
+        .map(|(power, left)| format!("{} {}", power.name(), left.as_secs_f32().ceil()));
+    std::iter::once(arsenal.weapons[player.weapon].name.clone())
+        .chain(powers)
+        .collect::<Vec<String>>()
+        .join("  ")
+}
+
+fn idle_view(app: &App, model: &Model, frame: Frame){
+    let win = app.window_rect();
+    let screen = app.draw();
+    screen.background().color(BLACK);
+    let draw = model.camera.view(&screen);
+
+    let pickup_font: Font = Font::from_bytes(model.credit_font.clone()).unwrap();
+    let local = model.net.as_ref().map(|session| session.local());
+    let view = view_of(&model.world, local.unwrap_or(0), model.alpha, win);
+    draw_world(&draw, &view, &model.world, model.alpha, &pickup_font);
+    particles::draw_particles(&draw, &view, &model.particles, model.alpha);
+    if model.debug.on{
+        debug::draw_shapes(&draw, &view, &model.world);
+    }
+
+    if model.world.large{
+        /* Clear of the top corner scores in a three or four ship game. On the
+         * screen rather than through the camera, so it holds still when that shakes. */
+        let top = if model.world.players.len() > 2 { win.top() - 65.0 } else { win.top() - 10.0 };
+        draw_radar(&screen, &view, &model.world, pt2(win.right() - 10.0, top));
+    }
+
+    if let Some(playback) = &model.playback{
+        let replay_font: Font = Font::from_bytes(model.credit_font.clone()).unwrap();
+        let paused = if playback.paused { "  PAUSED" } else { "" };
+        let status = format!("REPLAY x{}  tick {}{}", playback.speed(), model.world.frame, paused);
+        draw.text(&status)
+            .font(replay_font)
+            .font_size(16)
+            .no_line_wrap()
+            .xy(pt2(0.0, win.top() - 20.0));
+    }
 
     let actual_font: Font = Font::from_bytes(model.score_font.clone()).unwrap();
     
-    let score = format!("Score: {}", model.player.score);
-    draw.text(&score)
+    if model.world.players.len() > 1{
+        /* Bottom corners first, then the top ones for a full networked game */
+        for (i, player) in model.world.players.iter().enumerate(){
+            let you = if local == Some(i) { " (YOU)" } else { "" };
+            let score = format!("P{}{} Score: {} Lives: {}", i + 1, you, player.score, player.lives);
+            let x = if i % 2 == 0 { win.left() + 120.0 } else { win.right() - 120.0 };
+            let y = if i < 2 { win.bottom() + 30.0 } else { win.top() - 30.0 };
+            draw.text(&score)
+                .font(actual_font.clone())
+                .font_size(20)
+                .no_line_wrap()
+                .xy(pt2(x , y));
+
+            /* Timers sit on the side of the score nearer the middle of the screen */
+            let timers_y = if i < 2 { y + 20.0 } else { y - 20.0 };
+            draw.text(&power_timers(player, &model.world.arsenal))
+                .font(actual_font.clone())
+                .font_size(16)
+                .no_line_wrap()
+                .xy(pt2(x , timers_y));
+        }
+    }
+    else{
+        let score = format!("Score: {}", model.world.players[0].score);
+        draw.text(&score)
+            .font(actual_font.clone())
+            .font_size(20)
+            .no_line_wrap()
+            .xy(pt2(win.right() - 120.0 , win.bottom() + 30.0));
+
+        draw.text(&power_timers(&model.world.players[0], &model.world.arsenal))
+            .font(actual_font)
+            .font_size(16)
+            .no_line_wrap()
+            .xy(pt2(win.right() - 120.0 , win.bottom() + 50.0));
+    }
+
+    if model.world.mode == GameMode::Waves{
+        let actual_font: Font = Font::from_bytes(model.score_font.clone()).unwrap();
+        let wave = format!("Wave: {}", model.world.wave.number);
+        /* Co-op scores take both corners */
+        let position = if model.world.players.len() > 1 { pt2(0.0, win.bottom() + 30.0) } else { pt2(win.left() + 120.0 , win.bottom() + 30.0) };
+        draw.text(&wave)
+            .font(actual_font)
+            .font_size(20)
+            .no_line_wrap()
+            .xy(position);
+    }
+
+    if model.world.mode == GameMode::Waves && model.world.wave.in_intermission(){
+        let banner_font: Font = Font::from_bytes(model.raw_font.clone()).unwrap();
+        let banner = format!("WAVE {}", model.world.wave.number + 1);
+        draw.text(&banner)
+            .font(banner_font)
+            .no_line_wrap()
+            .font_size(50)
+            .xy(pt2(0.0, 40.0));
+
+        if model.world.wave.bonus > 0{
+            let bonus_font: Font = Font::from_bytes(model.credit_font.clone()).unwrap();
+            let bonus = format!("wave clear bonus +{}", model.world.wave.bonus);
+            draw.text(&bonus)
+                .font(bonus_font)
+                .no_line_wrap()
+                .font_size(20)
+                .xy(pt2(0.0, -20.0));
+        }
+    }
+
+    if model.debug.on{
+        debug::draw_stats(&screen, win, &model.debug_font, &model.debug, &model.world, model.particles.live(), model.net.as_ref());
+    }
+
+    /* Drawn square to the window, whatever the camera was doing when play stopped */
+    if model.camera.flash() > 0.0{
+        screen.rect()
+            .wh(win.wh())
+            .color(rgba(1.0, 1.0, 1.0, model.camera.flash()));
+    }
+
+    if let Some(pause) = &model.pause{
+        draw_pause_menu(&screen, win, model, pause);
+    }
+
+    draw.to_frame(app, &frame).unwrap();
+}
+
+fn draw_pause_menu(draw: &Draw, win: Rect, model: &Model, pause: &Pause){
+    draw.rect()
+        .wh(win.wh())
+        .color(rgba(0.0, 0.0, 0.0, 0.6));
+
+    let actual_font: Font = Font::from_bytes(model.raw_font.clone()).unwrap();
+    draw.text("PAUSED")
         .font(actual_font)
-        .font_size(20)
         .no_line_wrap()
-        .xy(pt2(win.right() - 120.0 , win.bottom() + 30.0));
+        .font_size(50)
+        .xy(pt2(0.0, 100.0));
 
-    draw.to_frame(app, &frame).unwrap();
+    let credit_font: Font = Font::from_bytes(model.credit_font.clone()).unwrap();
+    for (i, item) in PAUSE_ITEMS.iter().enumerate(){
+        let text = if i == pause.selection { format!("> {} <", item) } else { item.to_string() };
+        draw.text(&text)
+            .font(credit_font.clone())
+            .no_line_wrap()
+            .font_size(20)
+            .xy(pt2(0.0, 20.0 - i as f32 * 30.0));
+    }
 }
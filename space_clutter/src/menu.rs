@@ -0,0 +1,110 @@
+use nannou::prelude::*;
+use nannou::text::Font;
+
+/* Rows past this many scroll, keeping the cursor on screen */
+const VISIBLE_ROWS: usize = 11;
+const ROW_SPACING: f32 = 24.0;
+const FONT_SIZE: u32 = 16;
+/* Labels sit left of the middle, their values to the right */
+const LABEL_X: f32 = -190.0;
+const VALUE_X: f32 = 110.0;
+const COLUMN_WIDTH: f32 = 200.0;
+const SLIDER_SIZE: (f32, f32) = (100.0, 10.0);
+
+/* How a row shows its value and what Left, Right and Space do to it */
+pub enum Widget{
+    Slider(f32),
+    Toggle(bool),
+    List(String),
+    KeyCapture(String),
+    Button,
+}
+
+/* What a key, button or stick does in a menu, whatever it's bound to in play */
+#[derive(Copy,Clone,PartialEq)]
+pub enum Nav{
+    Up,
+    Down,
+    Left,
+    Right,
+    Select,
+    Back,
+}
+
+pub struct Row{
+    pub label: String,
+    pub widget: Widget,
+}
+
+/* Where the cursor is in a menu, and whether it's waiting on a key to bind */
+#[derive(Default)]
+pub struct Cursor{
+    pub selection: usize,
+    pub capturing: bool,
+}
+
+impl Cursor{
+    pub fn up(&mut self, count: usize){
+        self.selection = (self.selection + count - 1) % count;
+    }
+
+    pub fn down(&mut self, count: usize){
+        self.selection = (self.selection + 1) % count;
+    }
+}
+
+pub fn label(draw: &Draw, font: &Font, text: &str, x: f32, y: f32){
+    draw.text(text)
+        .font(font.clone())
+        .font_size(FONT_SIZE)
+        .no_line_wrap()
+        .left_justify()
+        .w_h(COLUMN_WIDTH, ROW_SPACING)
+        .x_y(x + COLUMN_WIDTH / 2.0, y);
+}
+
+/* A bar filled to `value` out of 1 with `text` beside it, starting at `x` */
+pub fn draw_slider(draw: &Draw, font: &Font, value: f32, text: &str, x: f32, y: f32){
+    let (w, h) = SLIDER_SIZE;
+    draw.rect()
+        .x_y(x + w / 2.0, y)
+        .w_h(w, h)
+        .no_fill()
+        .stroke(WHITE)
+        .stroke_weight(1.0);
+    draw.rect()
+        .x_y(x + w * value / 2.0, y)
+        .w_h(w * value, h)
+        .color(WHITE);
+    label(draw, font, text, x + w + 10.0, y);
+}
+
+/* The rows around the cursor, the picked one marked with an arrow */
+pub fn draw_rows(draw: &Draw, font: &Font, rows: &[Row], cursor: &Cursor, top: f32){
+    let first = cursor.selection.saturating_sub(VISIBLE_ROWS / 2).min(rows.len().saturating_sub(VISIBLE_ROWS));
+    let last = (first + VISIBLE_ROWS).min(rows.len());
+
+    if first > 0{
+        label(draw, font, "^", LABEL_X - 20.0, top + ROW_SPACING);
+    }
+    for (i, row) in rows.iter().enumerate().take(last).skip(first){
+        let y = top - (i - first) as f32 * ROW_SPACING;
+        let selected = i == cursor.selection;
+        if selected{
+            label(draw, font, ">", LABEL_X - 20.0, y);
+        }
+        label(draw, font, &row.label, LABEL_X, y);
+
+        match &row.widget{
+            Widget::Slider(value) => draw_slider(draw, font, *value, &format!("{:.0}%", value * 100.0), VALUE_X, y),
+            Widget::Toggle(on) => label(draw, font, if *on { "ON" } else { "OFF" }, VALUE_X, y),
+            Widget::List(choice) => label(draw, font, &format!("< {} >", choice), VALUE_X, y),
+            Widget::KeyCapture(_) if selected && cursor.capturing => label(draw, font, "[ press a key ]", VALUE_X, y),
+            Widget::KeyCapture(key) => label(draw, font, &format!("[ {} ]", key), VALUE_X, y),
+            Widget::Button => {}
+        }
+    }
+    if last < rows.len(){
+        label(draw, font, "v", LABEL_X - 20.0, top - VISIBLE_ROWS as f32 * ROW_SPACING);
+    }
+}
@@ -0,0 +1,583 @@
+use std::collections::VecDeque;
+use std::io::ErrorKind;
+use std::net::{SocketAddr, UdpSocket};
+use std::time::{Duration, Instant};
+
+use crate::replay::{self, Setup};
+use crate::rng::Rng;
+use crate::{world_step, Input, World, INPUT_BYTES, MAX_PLAYERS};
+
+/* Every peer builds its field from this, so they all start on the same rocks */
+pub const SESSION_SEED: u64 = 0x5350_4143_4543_4C54;
+/* Furthest a peer will guess ahead of the slowest remote input before waiting */
+const MAX_ROLLBACK: u32 = 8;
+const PACKET_MAGIC: [u8; 2] = *b"SC";
+/* Sent until answered, magic, player and the setup every peer has to share */
+const HELLO_MAGIC: [u8; 2] = *b"SH";
+const HELLO_HEADER: usize = 3;
+/* Magic, player, ack, first frame and input count */
+const PACKET_HEADER: usize = 12;
+const PACKET_MAX: usize = 512;
+
+/* Trouble added to outgoing packets, for trying the netcode out on loopback */
+#[derive(Copy,Clone,Default)]
+pub struct Link{
+    pub latency: Duration,
+    pub loss: f32,
+}
+
+/* Rollback session over UDP. Every peer steps the whole world with its own input
+ * straight away and a guess for everyone else's, the last input they sent with
+ * the trigger let go. Each packet repeats every input the other end hasn't
+ * acknowledged yet, so a lost one costs nothing but a little lag. When a real
+ * input turns out different from the guess, the world is put back to the
+ * snapshot before that frame and stepped forward again.
+ *
+ * Before any of that, each peer says how its game is set up, and inputs are only
+ * taken from a peer whose setup matches. One that doesn't is refused, and the
+ * game ends the session rather than playing out two different games. */
+pub struct Session{
+    socket: UdpSocket,
+    local: usize,
+    peers: Vec<Option<SocketAddr>>,
+    frame: u32,
+    history: Vec<[Input; MAX_PLAYERS]>,
+    known: [u32; MAX_PLAYERS],
+    acked: [u32; MAX_PLAYERS],
+    snapshots: VecDeque<World>,
+    rollback: Option<u32>,
+    link: Link,
+    outbox: VecDeque<(Instant, SocketAddr, Vec<u8>)>,
+    loss: Rng,
+    /* The setup we start with and the ones the other peers have sent */
+    setup: Option<Vec<u8>>,
+    heard: Vec<Option<Vec<u8>>>,
+    /* The first peer found with a different setup */
+    pub refused: Option<usize>,
+    /* How hard the link is working, for the debug overlay */
+    pub rollbacks: u32,
+    pub deepest: u32,
+    pub stalls: u32,
+}
+
+/* Seed, mode, preset, friendly fire and field size, as the hello carries them */
+fn setup_bytes(setup: &Setup) -> Vec<u8>{
+    let mut bytes = setup.seed.to_le_bytes().to_vec();
+    bytes.push(replay::mode_to_byte(setup.mode));
+    bytes.push(replay::preset_to_byte(setup.preset));
+    bytes.push(setup.friendly_fire as u8);
+    bytes.push(setup.large as u8);
+    bytes
+}
+
+fn read_u32(bytes: &[u8]) -> u32{
+    u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+}
+
+impl Session{
+    /* Plays as `local` out of everyone in `addresses`, listed in player order */
+    pub fn new(socket: UdpSocket, local: usize, addresses: &[SocketAddr], link: Link) -> std::io::Result<Session>{
+        socket.set_nonblocking(true)?;
+        Ok(Session{
+            socket,
+            local,
+            peers: addresses.iter()
+                .enumerate()
+                .map(|(i, &address)| if i == local { None } else { Some(address) })
+                .collect(),
+            frame: 0,
+            history: Vec::new(),
+            known: [0; MAX_PLAYERS],
+            acked: [0; MAX_PLAYERS],
+            snapshots: VecDeque::new(),
+            rollback: None,
+            link,
+            outbox: VecDeque::new(),
+            loss: Rng::new(local as u64 + 1),
+            setup: None,
+            heard: vec![None; addresses.len()],
+            refused: None,
+            rollbacks: 0,
+            deepest: 0,
+            stalls: 0,
+        })
+    }
+
+    pub fn players(&self) -> usize{
+        self.peers.len()
+    }
+
+    pub fn local(&self) -> usize{
+        self.local
+    }
+
+    /* Settles what this peer is playing, read from the world straight after its restart */
+    pub fn start(&mut self, setup: &Setup){
+        self.setup = Some(setup_bytes(setup));
+        for player in 0..self.players(){
+            self.check(player);
+        }
+    }
+
+    fn agreed(&self, player: usize) -> bool{
+        self.setup.is_some() && self.heard[player] == self.setup
+    }
+
+    fn check(&mut self, player: usize){
+        let differs = match (&self.setup, &self.heard[player]){
+            (Some(ours), Some(theirs)) => ours != theirs,
+            _ => false,
+        };
+        if differs && self.refused.is_none(){
+            log::error!("Player {} has a different seed, mode, preset, friendly fire or field size set, not playing with them", player + 1);
+            self.refused = Some(player);
+        }
+    }
+
+    fn remotes(&self) -> impl Iterator<Item = (usize, SocketAddr)> + '_{
+        self.peers.iter()
+            .enumerate()
+            .filter_map(|(player, address)| address.map(|address| (player, address)))
+    }
+
+    /* Oldest frame some player's input is still missing for */
+    fn confirmed(&self) -> u32{
+        self.known[..self.players()].iter().copied().min().unwrap_or(0)
+    }
+
+    fn row(&mut self, frame: u32) -> &mut [Input; MAX_PLAYERS]{
+        while self.history.len() <= frame as usize{
+            self.history.push([Input::default(); MAX_PLAYERS]);
+        }
+        &mut self.history[frame as usize]
+    }
+
+    /* Real inputs where we have them, guesses for the rest */
+    fn inputs(&mut self, frame: u32) -> Vec<Input>{
+        for player in 0..self.players(){
+            let known = self.known[player];
+            if frame >= known{
+                let guess = match known{
+                    0 => Input::default(),
+                    _ => Input{ fire: false, switch: false, ..self.history[known as usize - 1][player] },
+                };
+                self.row(frame)[player] = guess;
+            }
+        }
+        let players = self.players();
+        self.row(frame)[..players].to_vec()
+    }
+
+    fn simulate(&mut self, world: &mut World){
+        let inputs = self.inputs(self.frame);
+        self.snapshots.push_back(world.clone());
+        world.effects.clear();
+        world_step(world, &inputs);
+        self.frame += 1;
+    }
+
+    /* Steps one frame with the local input, or returns false without stepping
+     * when the other peers have fallen too far behind to keep guessing */
+    pub fn advance(&mut self, world: &mut World, input: Input, now: Instant) -> bool{
+        self.receive(world);
+
+        let slowest = self.remotes().map(|(player, _)| self.known[player]).min().unwrap_or(u32::MAX);
+        let advanced = self.frame < slowest.saturating_add(MAX_ROLLBACK);
+        if advanced{
+            let frame = self.frame;
+            let local = self.local;
+            self.row(frame)[local] = input;
+            self.known[local] = frame + 1;
+            self.simulate(world);
+        }
+        else{
+            self.stalls += 1;
+        }
+
+        self.send(now);
+        advanced
+    }
+
+    /* Trades packets and applies late inputs without moving on a frame */
+    pub fn pump(&mut self, world: &mut World, now: Instant){
+        self.receive(world);
+        self.send(now);
+    }
+
+    fn receive(&mut self, world: &mut World){
+        let mut buffer = [0u8; PACKET_MAX];
+        loop{
+            match self.socket.recv_from(&mut buffer){
+                Ok((length, _)) => self.handle_packet(&buffer[..length]),
+                /* Windows reports unreachable peers here, they may just not be up yet */
+                Err(error) if error.kind() == ErrorKind::ConnectionReset => continue,
+                Err(_) => break,
+            }
+        }
+
+        if let Some(frame) = self.rollback.take(){
+            self.resimulate(world, frame);
+        }
+
+        let confirmed = self.confirmed();
+        while self.snapshots.front().is_some_and(|snapshot| snapshot.frame < confirmed){
+            self.snapshots.pop_front();
+        }
+    }
+
+    fn handle_hello(&mut self, packet: &[u8]){
+        let player = packet[2] as usize;
+        if player >= self.players() || player == self.local{
+            return;
+        }
+        self.heard[player] = Some(packet[HELLO_HEADER..].to_vec());
+        self.check(player);
+    }
+
+    fn handle_packet(&mut self, packet: &[u8]){
+        if packet.len() > HELLO_HEADER && packet[..2] == HELLO_MAGIC{
+            self.handle_hello(packet);
+            return;
+        }
+        if packet.len() < PACKET_HEADER || packet[..2] != PACKET_MAGIC{
+            return;
+        }
+
+        let player = packet[2] as usize;
+        if player >= self.players() || player == self.local || !self.agreed(player){
+            return;
+        }
+
+        self.acked[player] = self.acked[player].max(read_u32(&packet[3..]));
+        let start = read_u32(&packet[7..]);
+        let count = packet[11] as usize;
+        let inputs = &packet[PACKET_HEADER..];
+        if inputs.len() < count * INPUT_BYTES{
+            return;
+        }
+
+        for (i, bytes) in inputs.chunks_exact(INPUT_BYTES).take(count).enumerate(){
+            let frame = start + i as u32;
+            if frame < self.known[player]{
+                continue;
+            }
+            if frame > self.known[player]{
+                break;
+            }
+
+            let input = Input::from_bytes(bytes);
+            /* Already stepped on a guess, and the guess was wrong */
+            if frame < self.frame && self.history[frame as usize][player] != input{
+                self.rollback = Some(self.rollback.map_or(frame, |earliest| earliest.min(frame)));
+            }
+            self.row(frame)[player] = input;
+            self.known[player] = frame + 1;
+        }
+    }
+
+    /* Puts the world back to how it was before `frame` and steps it up to date */
+    fn resimulate(&mut self, world: &mut World, frame: u32){
+        let oldest = self.snapshots.front().expect("No snapshot to roll back to").frame;
+        let index = (frame - oldest) as usize;
+        *world = self.snapshots[index].clone();
+        self.snapshots.truncate(index);
+
+        let depth = self.frame - frame;
+        self.rollbacks += 1;
+        self.deepest = self.deepest.max(depth);
+
+        let present = self.frame;
+        self.frame = frame;
+        while self.frame < present{
+            self.simulate(world);
+        }
+
+        /* Those frames have been seen and heard already */
+        world.effects.clear();
+    }
+
+    fn send(&mut self, now: Instant){
+        let local = self.local;
+        let remotes: Vec<(usize, SocketAddr)> = self.remotes().collect();
+        for (player, address) in remotes{
+            /* Nothing acknowledged yet, so they may not have heard how we're set up */
+            if let Some(setup) = self.setup.as_ref().filter(|_| self.acked[player] == 0){
+                let mut hello = Vec::with_capacity(HELLO_HEADER + setup.len());
+                hello.extend_from_slice(&HELLO_MAGIC);
+                hello.push(local as u8);
+                hello.extend_from_slice(setup);
+                if self.loss.range(0.0, 1.0) >= self.link.loss{
+                    self.outbox.push_back((now + self.link.latency, address, hello));
+                }
+            }
+
+            let start = self.acked[player].min(self.known[local]);
+            let count = (self.known[local] - start).min(u8::MAX as u32);
+
+            let mut packet = Vec::with_capacity(PACKET_HEADER + count as usize * INPUT_BYTES);
+            packet.extend_from_slice(&PACKET_MAGIC);
+            packet.push(local as u8);
+            packet.extend_from_slice(&self.known[player].to_le_bytes());
+            packet.extend_from_slice(&start.to_le_bytes());
+            packet.push(count as u8);
+            for frame in start..start + count{
+                packet.extend_from_slice(&self.history[frame as usize][local].to_bytes());
+            }
+
+            if self.loss.range(0.0, 1.0) >= self.link.loss{
+                self.outbox.push_back((now + self.link.latency, address, packet));
+            }
+        }
+
+        while self.outbox.front().is_some_and(|(due, _, _)| *due <= now){
+            let (_, address, packet) = self.outbox.pop_front().unwrap();
+            self.socket.send_to(&packet, address).ok();
+        }
+    }
+}
+
+/* `--net N ADDR ADDR...` plays as player N of the listed addresses, one per player in order */
+pub fn session_from_args() -> Option<Session>{
+    let args: Vec<String> = std::env::args().collect();
+    let position = args.iter().position(|arg| arg == "--net")?;
+    let local: Option<usize> = args.get(position + 1).and_then(|arg| arg.parse().ok());
+    let addresses: Vec<SocketAddr> = args[position + 1..].iter()
+        .skip(1)
+        .map_while(|arg| arg.parse().ok())
+        .collect();
+
+    let local = match local{
+        Some(local) if local < addresses.len() && (2..=MAX_PLAYERS).contains(&addresses.len()) => local,
+        _ => {
+            log::error!("--net needs a player number then 2 to {} addresses", MAX_PLAYERS);
+            return None;
+        }
+    };
+
+    match UdpSocket::bind(addresses[local]).and_then(|socket| Session::new(socket, local, &addresses, Link::default())){
+        Ok(session) => Some(session),
+        Err(error) => {
+            log::error!("Could not open network session: {}", error);
+            None
+        }
+    }
+}
+
+/* FNV-1a over the exact bits of everything that matters to play */
+pub fn world_hash(world: &World) -> u64{
+    let mut hash: u64 = 0xCBF2_9CE4_8422_2325;
+    let mut write = |bytes: &[u8]| {
+        for &byte in bytes{
+            hash = (hash ^ byte as u64).wrapping_mul(0x0100_0000_01B3);
+        }
+    };
+
+    write(&world.frame.to_le_bytes());
+    write(&world.rng.state().to_le_bytes());
+    write(&world.difficulty.max_asteroids.to_le_bytes());
+    write(&world.difficulty.asteroid_speed.to_bits().to_le_bytes());
+    write(&world.wave.number.to_le_bytes());
+
+    for player in &world.players{
+        for value in [player.position.x, player.position.y, player.rotation, player.velocity.x, player.velocity.y]{
+            write(&value.to_bits().to_le_bytes());
+        }
+        write(&player.score.to_le_bytes());
+        write(&player.lives.to_le_bytes());
+        write(&[player.alive as u8, player.fire_queued as u8]);
+        write(&(player.weapon as u32).to_le_bytes());
+        write(&player.respawn.as_nanos().to_le_bytes());
+        write(&player.invulnerable.as_nanos().to_le_bytes());
+        for left in [player.powers.shield, player.powers.spread, player.powers.rapid, player.reload]{
+            write(&left.as_nanos().to_le_bytes());
+        }
+        for missile in &player.missile{
+            for value in [missile.position.x, missile.position.y, missile.rotation, missile.velocity.x, missile.velocity.y]{
+                write(&value.to_bits().to_le_bytes());
+            }
+            write(&missile.life.as_nanos().to_le_bytes());
+        }
+    }
+
+    write(&world.calm.as_nanos().to_le_bytes());
+    for well in &world.wells{
+        for value in [well.position.x, well.position.y]{
+            write(&value.to_bits().to_le_bytes());
+        }
+    }
+    for pickup in &world.pickups{
+        for value in [pickup.position.x, pickup.position.y]{
+            write(&value.to_bits().to_le_bytes());
+        }
+        write(&pickup.life.as_nanos().to_le_bytes());
+        write(&[pickup.power as u8]);
+    }
+
+    for asteroid in &world.asteroid{
+        for value in [asteroid.position.x, asteroid.position.y, asteroid.rotation, asteroid.size, asteroid.velocity.x, asteroid.velocity.y]{
+            write(&value.to_bits().to_le_bytes());
+        }
+        write(&asteroid.hp.to_le_bytes());
+        for point in &asteroid.points{
+            write(&point.x.to_bits().to_le_bytes());
+            write(&point.y.to_bits().to_le_bytes());
+        }
+    }
+
+    hash
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use crate::difficulty::{Difficulty, Preset, Presets};
+    use crate::TIMESTEP;
+
+    const TEST_FRAMES: u32 = 900;
+    /* Latency in ms and fraction of packets dropped, both ways */
+    const TEST_LINKS: [(u64, f32); 4] = [(0, 0.0), (50, 0.05), (120, 0.2), (200, 0.4)];
+    /* Settled or not, a test case gives up after this many ticks */
+    const TEST_TICK_LIMIT: u32 = TEST_FRAMES * 20;
+
+    impl Session{
+        /* Every frame up to `frames` stepped on real inputs only */
+        fn settled(&self, frames: u32) -> bool{
+            self.frame >= frames && self.confirmed() >= frames && self.rollback.is_none()
+        }
+    }
+
+    /* Sessions for every player over loopback, each started on `setups` */
+    fn test_sessions(setups: &[Setup], link: Link) -> Vec<Session>{
+        let sockets: Vec<UdpSocket> = setups.iter()
+            .map(|_| UdpSocket::bind("127.0.0.1:0").expect("Could not bind loopback socket"))
+            .collect();
+        let addresses: Vec<SocketAddr> = sockets.iter().map(|socket| socket.local_addr().unwrap()).collect();
+        sockets.into_iter()
+            .zip(setups)
+            .enumerate()
+            .map(|(local, (socket, setup))| {
+                let mut session = Session::new(socket, local, &addresses, link).unwrap();
+                session.start(setup);
+                session
+            })
+            .collect()
+    }
+
+    fn test_world(players: usize) -> World{
+        let mut world = World::new(Difficulty::new(Presets::load(), Preset::Normal, None), SESSION_SEED);
+        world.restart(players, SESSION_SEED);
+        world
+    }
+
+    /* Made-up pilot for the test, holds random controls for random stretches */
+    fn test_script(player: usize) -> Vec<Input>{
+        let mut rng = Rng::new(SESSION_SEED ^ player as u64);
+        let mut script = Vec::new();
+        while script.len() < TEST_FRAMES as usize{
+            let held = Input{
+                left: rng.below(3) == 0,
+                right: rng.below(3) == 0,
+                thrust: rng.below(2) == 0,
+                fire: false,
+                trigger: rng.below(2) == 0,
+                switch: false,
+                /* Some stretches on a stick, so its byte crosses the wire too */
+                turn: if rng.below(4) == 0 { (rng.below(255) as i32 - 127) as i8 } else { 0 },
+            };
+            for _ in 0..rng.below(30) + 1{
+                script.push(Input{ fire: rng.below(8) == 0, switch: rng.below(200) == 0, ..held });
+            }
+        }
+        script.truncate(TEST_FRAMES as usize);
+        script
+    }
+
+    /* Peers on loopback through a bad link end up bit for bit where one world stepped straight through does */
+    fn check_link(players: usize, link: Link){
+        let scripts: Vec<Vec<Input>> = (0..players).map(test_script).collect();
+        let mut worlds: Vec<World> = (0..players).map(|_| test_world(players)).collect();
+        let setups: Vec<Setup> = worlds.iter().map(replay::setup_of).collect();
+        let mut sessions = test_sessions(&setups, link);
+
+        /* The clock is faked so latency costs no real time, one tick per pass */
+        let mut now = Instant::now();
+        let mut ticks = 0;
+        while sessions.iter().any(|session| !session.settled(TEST_FRAMES)) && ticks < TEST_TICK_LIMIT{
+            for (session, world) in sessions.iter_mut().zip(&mut worlds){
+                if session.frame < TEST_FRAMES{
+                    let input = scripts[session.local][session.frame as usize];
+                    session.advance(world, input, now);
+                }
+                else{
+                    session.pump(world, now);
+                }
+            }
+            now += TIMESTEP;
+            ticks += 1;
+        }
+
+        /* The same inputs stepped straight through with nothing in the way */
+        let mut reference = test_world(players);
+        for frame in 0..TEST_FRAMES as usize{
+            let inputs: Vec<Input> = scripts.iter().map(|script| script[frame]).collect();
+            world_step(&mut reference, &inputs);
+        }
+
+        let expected = world_hash(&reference);
+        for world in &worlds{
+            assert_eq!(world.frame, TEST_FRAMES, "{} players at {:?} ms stopped short", players, link.latency);
+            assert_eq!(world_hash(world), expected, "{} players at {:?} ms disagree", players, link.latency);
+        }
+        /* Guessing the other ships' inputs has to go wrong sometimes, or nothing rolled back */
+        assert!(sessions.iter().any(|session| session.rollbacks > 0));
+    }
+
+    fn check_links(players: usize){
+        for &(latency, loss) in &TEST_LINKS{
+            check_link(players, Link{ latency: Duration::from_millis(latency), loss });
+        }
+    }
+
+    #[test]
+    fn two_peers_agree(){
+        check_links(2);
+    }
+
+    #[test]
+    fn four_peers_agree(){
+        check_links(MAX_PLAYERS);
+    }
+
+    /* Two peers started on these worlds, and who each of them turned away */
+    fn refused(world: World, other: World) -> [Option<usize>; 2]{
+        let mut sessions = test_sessions(&[replay::setup_of(&world), replay::setup_of(&other)], Link::default());
+        let mut worlds = [world, other];
+
+        let now = Instant::now();
+        for _ in 0..MAX_ROLLBACK * 4{
+            for (session, world) in sessions.iter_mut().zip(&mut worlds){
+                session.advance(world, Input::default(), now);
+            }
+        }
+
+        /* Neither got past guessing */
+        for world in &worlds{
+            assert!(world.frame <= MAX_ROLLBACK);
+        }
+        [sessions[0].refused, sessions[1].refused]
+    }
+
+    #[test]
+    fn mismatched_setups_are_refused(){
+        let world = test_world(2);
+        let mut other = test_world(2);
+        other.mode = other.mode.toggle();
+        assert_eq!(refused(world, other), [Some(1), Some(0)]);
+
+        let world = test_world(2);
+        let mut other = test_world(2);
+        other.set_large(true);
+        other.restart(2, SESSION_SEED);
+        assert_eq!(refused(world, other), [Some(1), Some(0)]);
+    }
+}
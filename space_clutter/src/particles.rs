@@ -0,0 +1,176 @@
+use nannou::prelude::*;
+
+use crate::spatial::View;
+use crate::ship::heading;
+use crate::{interpolate, Asteroid, Player, TIMESTEP};
+
+/* Oldest particles get recycled once the pool is full */
+const PARTICLE_CAPACITY: usize = 1024;
+const PARTICLE_WEIGHT: f32 = 2.0;
+
+const SPARK_COUNT: u32 = 10;
+const SPARK_SPEED: (f32, f32) = (60.0, 200.0);
+const SPARK_LIFE: (f32, f32) = (0.2, 0.5);
+const SPARK_LENGTH: f32 = 3.0;
+
+const SHATTER: Burst = Burst{ speed: (20.0, 70.0), spin: 4.0, life: (0.6, 1.2) };
+
+const EXHAUST_SPEED: (f32, f32) = (90.0, 140.0);
+const EXHAUST_SPREAD: f32 = 0.35;
+const EXHAUST_LIFE: (f32, f32) = (0.15, 0.3);
+const EXHAUST_LENGTH: f32 = 2.0;
+/* How far behind the ship's centre the flame starts */
+const EXHAUST_OFFSET: f32 = 22.0;
+
+pub const BREAKUP_LIFE: f32 = 1.5;
+const BREAKUP: Burst = Burst{ speed: (15.0, 45.0), spin: 2.5, life: (BREAKUP_LIFE, BREAKUP_LIFE) };
+
+/* How line segments fly away from whatever broke apart */
+struct Burst{
+    speed: (f32, f32),
+    spin: f32,
+    life: (f32, f32),
+}
+
+#[derive(Copy,Clone)]
+struct Particle{
+    position: Point2,
+    previous_position: Point2,
+    velocity: Vec2,
+    rotation: f32,
+    previous_rotation: f32,
+    spin: f32,
+    half_length: f32,
+    life: f32,
+    lifetime: f32,
+}
+
+pub struct Particles{
+    pool: Vec<Particle>,
+    next: usize,
+}
+
+fn rotate_point(point: Vec2, angle: f32) -> Vec2{
+    let (sin, cos) = angle.sin_cos();
+    vec2(point.x * cos - point.y * sin, point.x * sin + point.y * cos)
+}
+
+/* Particles are only for show and skipped when the netcode replays frames, so
+ * they roll nannou's generator and leave the world's seeded one alone */
+fn random_direction() -> Vec2{
+    heading(random_range(0.0, TAU))
+}
+
+impl Particles{
+    pub fn new() -> Particles{
+        Particles{
+            pool: Vec::with_capacity(PARTICLE_CAPACITY),
+            next: 0,
+        }
+    }
+
+    pub fn clear(&mut self){
+        self.pool.clear();
+        self.next = 0;
+    }
+
+    fn spawn(&mut self, position: Point2, velocity: Vec2, rotation: f32, spin: f32, half_length: f32, lifetime: f32){
+        let particle = Particle{
+            position,
+            previous_position: position,
+            velocity,
+            rotation,
+            previous_rotation: rotation,
+            spin,
+            half_length,
+            life: lifetime,
+            lifetime,
+        };
+
+        if self.pool.len() < PARTICLE_CAPACITY{
+            self.pool.push(particle);
+        }
+        else{
+            self.pool[self.next] = particle;
+            self.next = (self.next + 1) % PARTICLE_CAPACITY;
+        }
+    }
+
+    pub fn live(&self) -> usize{
+        self.pool.iter().filter(|particle| particle.life > 0.0).count()
+    }
+
+    /* Dead particles stay in the pool until their slot is reused */
+    pub fn update(&mut self){
+        let dt = TIMESTEP.as_secs_f32();
+        for particle in self.pool.iter_mut().filter(|particle| particle.life > 0.0){
+            particle.previous_position = particle.position;
+            particle.previous_rotation = particle.rotation;
+            particle.position += particle.velocity * dt;
+            particle.rotation += particle.spin * dt;
+            particle.life -= dt;
+        }
+    }
+
+    /* Streaks thrown out in every direction, lined up with their travel */
+    pub fn emit_sparks(&mut self, position: Point2){
+        for _ in 0..SPARK_COUNT{
+            let velocity = random_direction() * random_range(SPARK_SPEED.0, SPARK_SPEED.1);
+            let rotation = velocity.y.atan2(velocity.x);
+            self.spawn(position, velocity, rotation, 0.0, SPARK_LENGTH, random_range(SPARK_LIFE.0, SPARK_LIFE.1));
+        }
+    }
+
+    /* Every edge of the outline flies off on its own, carrying the rock's drift */
+    pub fn emit_shatter(&mut self, asteroid: &Asteroid){
+        let drift = asteroid.velocity / TIMESTEP.as_secs_f32();
+        for edge in asteroid.points.windows(2){
+            let start = asteroid.position + rotate_point(edge[0], asteroid.rotation);
+            let end = asteroid.position + rotate_point(edge[1], asteroid.rotation);
+            self.emit_segment(asteroid.position, start, end, drift, &SHATTER);
+        }
+    }
+
+    /* A short flicker out of the back of the ship, called every step while thrusting */
+    pub fn emit_exhaust(&mut self, player: &Player){
+        let back = -heading(player.rotation);
+        let position = player.position + back * EXHAUST_OFFSET;
+        let direction = rotate_point(back, random_range(-EXHAUST_SPREAD, EXHAUST_SPREAD));
+        let velocity = player.velocity + direction * random_range(EXHAUST_SPEED.0, EXHAUST_SPEED.1);
+        let rotation = direction.y.atan2(direction.x);
+        self.spawn(position, velocity, rotation, 0.0, EXHAUST_LENGTH, random_range(EXHAUST_LIFE.0, EXHAUST_LIFE.1));
+    }
+
+    /* The hull outline comes apart into its separate edges */
+    pub fn emit_breakup(&mut self, player: &Player, hull: &[Point2]){
+        for i in 0..hull.len(){
+            let start = player.position + rotate_point(hull[i], player.rotation);
+            let end = player.position + rotate_point(hull[(i + 1) % hull.len()], player.rotation);
+            self.emit_segment(player.position, start, end, player.velocity, &BREAKUP);
+        }
+    }
+
+    fn emit_segment(&mut self, centre: Point2, start: Point2, end: Point2, drift: Vec2, burst: &Burst){
+        let middle = (start + end) / 2.0;
+        let outward = (middle - centre).try_normalize().unwrap_or_else(random_direction);
+        let velocity = drift + outward * random_range(burst.speed.0, burst.speed.1);
+        let along = end - start;
+        let spin = random_range(-burst.spin, burst.spin);
+        let lifetime = if burst.life.0 < burst.life.1 { random_range(burst.life.0, burst.life.1) } else { burst.life.0 };
+        self.spawn(middle, velocity, along.y.atan2(along.x), spin, along.length() / 2.0, lifetime);
+    }
+}
+
+pub fn draw_particles(draw: &Draw, view: &View, particles: &Particles, alpha: f32){
+    for particle in particles.pool.iter().filter(|particle| particle.life > 0.0){
+        let position = view.place(interpolate(particle.previous_position, particle.position, alpha));
+        let rotation = particle.previous_rotation + (particle.rotation - particle.previous_rotation) * alpha;
+        let half = rotate_point(vec2(particle.half_length, 0.0), rotation);
+        let fade = particle.life / particle.lifetime;
+        draw.line()
+            .start(position - half)
+            .end(position + half)
+            .weight(PARTICLE_WEIGHT)
+            .color(rgba(1.0, 1.0, 1.0, fade));
+    }
+}
@@ -0,0 +1,218 @@
+use nannou::prelude::*;
+
+use crate::difficulty::{Difficulty, Preset, Presets};
+use crate::ship::{angle_between, heading, rotation_of};
+use crate::spatial::wrapped_offset;
+use crate::wave::GameMode;
+use crate::{world_step, Input, World, SPACESHIP_HEIGHT, TIMESTEP};
+
+/* Steps between shots, about what a keen thumb manages on the fire button */
+const PILOT_FIRE_INTERVAL: u32 = 10;
+/* A rock due to pass this close within this many steps gets dodged */
+const PILOT_DANGER_MARGIN: f32 = 12.0;
+const PILOT_DANGER_STEPS: f32 = 40.0;
+/* Turning stops inside this, a little over one step's worth of turn */
+const PILOT_TURN_DEADBAND: f32 = 0.07;
+/* Getting this much closer than the horizon to a gravity well means flying straight out */
+const PILOT_WELL_MARGIN: f32 = 120.0;
+/* Only burn towards an escape heading this close to the nose */
+const PILOT_ESCAPE_ALIGNMENT: f32 = 0.5;
+
+const SOAK_GAMES: u32 = 1000;
+/* Ten minutes of play, a game still going by then is counted as a timeout */
+const SOAK_STEP_LIMIT: u32 = 60 * 60 * 10;
+
+/* Flies a ship through the same inputs a player would press. The only thing it
+ * remembers between steps is when it last fired. */
+#[derive(Default)]
+pub struct Pilot{
+    cooldown: u32,
+}
+
+/* Steps until a shot at `speed` from the origin meets something at `offset` moving at `velocity` */
+fn intercept_time(offset: Vec2, velocity: Vec2, speed: f32) -> Option<f32>{
+    let a = velocity.length_squared() - speed * speed;
+    let b = 2.0 * offset.dot(velocity);
+    let c = offset.length_squared();
+
+    if a.abs() < f32::EPSILON{
+        return if b < 0.0 { Some(-c / b) } else { None };
+    }
+
+    let discriminant = b * b - 4.0 * a * c;
+    if discriminant < 0.0{
+        return None;
+    }
+
+    let root = discriminant.sqrt();
+    [(-b - root) / (2.0 * a), (-b + root) / (2.0 * a)]
+        .into_iter()
+        .filter(|&time| time >= 0.0)
+        .reduce(f32::min)
+}
+
+/* When, in steps, and how near something at `offset` drifting at `velocity` passes the origin */
+fn closest_approach(offset: Vec2, velocity: Vec2) -> (f32, f32){
+    let speed = velocity.length_squared();
+    let time = if speed > 0.0 { (-offset.dot(velocity) / speed).max(0.0) } else { 0.0 };
+    (time, (offset + velocity * time).length())
+}
+
+fn turn_towards(input: &mut Input, rotation: f32, target: f32){
+    let turn = angle_between(rotation, target);
+    input.left = turn > PILOT_TURN_DEADBAND;
+    input.right = turn < -PILOT_TURN_DEADBAND;
+}
+
+impl Pilot{
+    pub fn new() -> Pilot{
+        Pilot{ cooldown: 0 }
+    }
+
+    /* Dodges whatever is about to hit, otherwise swings onto the nearest rock it
+     * can reach and fires once lined up. Everything is measured through the
+     * wrap, but on one screen shots vanish at the edge so only on-screen
+     * intercepts count. In a large world they count within the shot's range. */
+    pub fn fly(&mut self, world: &World, index: usize) -> Input{
+        self.cooldown = self.cooldown.saturating_sub(1);
+
+        let mut input = Input::default();
+        let player = match world.players.get(index){
+            Some(player) if player.alive => player,
+            _ => return input,
+        };
+
+        let win = world.field;
+        let ship_velocity = player.velocity * TIMESTEP.as_secs_f32();
+
+        let weapon = &world.arsenal.weapons[player.weapon];
+        let shot_speed = weapon.speed;
+        let shot_range = weapon.speed * weapon.lifetime / TIMESTEP.as_secs_f32();
+        let mut danger: Option<(f32, Vec2)> = None;
+        let mut target: Option<(f32, f32, f32)> = None;
+        for asteroid in &world.asteroid{
+            let offset = wrapped_offset(asteroid.position - player.position, win);
+            let velocity = asteroid.velocity;
+
+            let (time, distance) = closest_approach(offset, velocity - ship_velocity);
+            let reach = (asteroid.size + SPACESHIP_HEIGHT) / 2.0 + PILOT_DANGER_MARGIN;
+            if distance < reach && time < PILOT_DANGER_STEPS && danger.is_none_or(|(soonest, _)| time < soonest){
+                danger = Some((time, offset));
+            }
+
+            if let Some(time) = intercept_time(offset, velocity, shot_speed){
+                let aim = offset + velocity * time;
+                let nearest = target.is_none_or(|(closest, _, _)| offset.length() < closest);
+                let reachable = if world.large { aim.length() < shot_range } else { win.contains(player.position + aim) };
+                if nearest && reachable{
+                    /* Anything inside the rock's half-width still hits */
+                    let tolerance = (asteroid.size / 2.0).atan2(aim.length());
+                    target = Some((offset.length(), rotation_of(aim), tolerance));
+                }
+            }
+        }
+
+        /* A well close enough to fall into trumps everything else */
+        let well = world.wells.iter()
+            .map(|well| wrapped_offset(well.position - player.position, win))
+            .find(|offset| offset.length() < world.hazards.horizon + PILOT_WELL_MARGIN);
+        if let Some(offset) = well{
+            turn_towards(&mut input, player.rotation, rotation_of(-offset));
+            input.thrust = heading(player.rotation).dot(-offset.normalize_or_zero()) > PILOT_ESCAPE_ALIGNMENT;
+            return input;
+        }
+
+        match (danger, target){
+            (Some((_, offset)), _) => {
+                /* Slip sideways out of the rock's path, whichever side is nearer the nose */
+                let mut away = vec2(offset.y, -offset.x).normalize_or_zero();
+                if heading(player.rotation).dot(away) < 0.0{
+                    away = -away;
+                }
+                turn_towards(&mut input, player.rotation, rotation_of(away));
+                input.thrust = heading(player.rotation).dot(away) > PILOT_ESCAPE_ALIGNMENT;
+            }
+            (None, Some((_, aim, _))) => turn_towards(&mut input, player.rotation, aim),
+            (None, None) => {}
+        }
+
+        if let Some((_, aim, tolerance)) = target{
+            if self.cooldown == 0 && angle_between(player.rotation, aim).abs() < tolerance{
+                input.fire = true;
+                self.cooldown = PILOT_FIRE_INTERVAL;
+            }
+        }
+
+        input
+    }
+}
+
+fn percentile(sorted: &[u32], fraction: f32) -> u32{
+    sorted[((sorted.len() - 1) as f32 * fraction).round() as usize]
+}
+
+/* Plays `games` solo games with the pilot from seeds 0 up, for balancing */
+fn soak(preset: Preset, mode: GameMode, games: u32){
+    let mut scores = Vec::new();
+    let mut seconds = 0.0;
+    let mut waves = 0;
+    let mut timeouts = 0;
+
+    for seed in 0..games as u64{
+        let mut world = World::new(Difficulty::new(Presets::load(), preset, None), seed);
+        world.mode = mode;
+        world.restart(1, seed);
+
+        let mut pilot = Pilot::new();
+        while !world.all_crashed() && world.frame < SOAK_STEP_LIMIT{
+            let input = pilot.fly(&world, 0);
+            world_step(&mut world, &[input]);
+        }
+
+        if !world.all_crashed(){
+            timeouts += 1;
+        }
+        scores.push(world.players[0].score);
+        seconds += world.frame as f32 * TIMESTEP.as_secs_f32();
+        waves += world.wave.number;
+    }
+
+    scores.sort_unstable();
+    let mean = scores.iter().sum::<u32>() as f32 / games as f32;
+    let wave = if mode == GameMode::Waves { format!("{:.1}", waves as f32 / games as f32) } else { "-".to_string() };
+    println!("{:>8} {:>8} {:>6} {:>8.1} {:>6} {:>6} {:>6} {:>6} {:>10.1} {:>6} {:>8}",
+        preset.name(),
+        mode.name(),
+        games,
+        mean,
+        percentile(&scores, 0.1),
+        percentile(&scores, 0.5),
+        percentile(&scores, 0.9),
+        scores[scores.len() - 1],
+        seconds / games as f32,
+        wave,
+        timeouts);
+}
+
+/* Headless balance run over every preset and mode, `--soak [GAMES]` */
+pub fn run_soak_test(){
+    let args: Vec<String> = std::env::args().collect();
+    let games = args.iter()
+        .position(|arg| arg == "--soak")
+        .and_then(|position| args.get(position + 1))
+        .and_then(|arg| arg.parse().ok())
+        .filter(|&games| games > 0)
+        .unwrap_or(SOAK_GAMES);
+
+    let mut rows = Vec::new();
+    for preset in [Preset::Easy, Preset::Normal, Preset::Arcade]{
+        for mode in [GameMode::Endless, GameMode::Waves]{
+            rows.push((preset, mode));
+        }
+    }
+
+    println!("{:>8} {:>8} {:>6} {:>8} {:>6} {:>6} {:>6} {:>6} {:>10} {:>6} {:>8}", "preset", "mode", "games", "mean", "p10", "p50", "p90", "best", "survived s", "wave", "timeouts");
+    for (preset, mode) in rows{
+        soak(preset, mode, games);
+    }
+}
@@ -0,0 +1,217 @@
+use nannou::prelude::*;
+use std::time::Duration;
+
+use crate::rng::Rng;
+use crate::spatial::{wrap_point, wrapped_offset};
+use crate::{playfield, view_of, Asteroid, Effect, Player, World, SPACESHIP_PEAK, TIMESTEP};
+
+/* Chance a rock that gets shot down leaves something behind */
+const DROP_CHANCE: f32 = 0.08;
+/* Relative odds of each drop, smart bombs turn up a quarter as often as the rest */
+const DROP_WEIGHTS: [(Power, u32); 4] = [
+    (Power::Shield, 4),
+    (Power::Spread, 4),
+    (Power::Rapid, 4),
+    (Power::Bomb, 1),
+];
+pub const PICKUP_RADIUS: f32 = 10.0;
+const PICKUP_LIFE: Duration = Duration::from_secs(10);
+/* Pickups blink for this long before they vanish */
+pub const PICKUP_WARNING: Duration = Duration::from_secs(3);
+
+const SHIELD_TIME: Duration = Duration::from_secs(10);
+const SPREAD_TIME: Duration = Duration::from_secs(10);
+const RAPID_TIME: Duration = Duration::from_secs(10);
+/* Nothing new drifts in for this long after a smart bomb */
+const BOMB_CALM: Duration = Duration::from_secs(3);
+/* Once the shield soaks a hit the ship is safe this long, so the same rock can't finish it off */
+const SHIELD_GRACE: Duration = Duration::from_secs(1);
+/* The outer shots of a spread fan out this far either side of the nose */
+const SPREAD_ANGLE: f32 = 0.2;
+
+#[derive(Copy,Clone,PartialEq)]
+pub enum Power{
+    Shield,
+    Spread,
+    Rapid,
+    Bomb,
+}
+
+impl Power{
+    pub fn name(self) -> &'static str{
+        match self{
+            Power::Shield => "SHIELD",
+            Power::Spread => "SPREAD",
+            Power::Rapid => "RAPID",
+            Power::Bomb => "BOMB",
+        }
+    }
+}
+
+/* A drop drifting with the field until someone flies into it or it runs out */
+#[derive(Clone)]
+pub struct Pickup{
+    pub power: Power,
+    pub position: Point2,
+    pub previous_position: Point2,
+    pub velocity: Vec2,
+    pub life: Duration,
+}
+
+/* Time left on each power a ship is holding */
+#[derive(Copy,Clone,Default)]
+pub struct Powers{
+    pub shield: Duration,
+    pub spread: Duration,
+    pub rapid: Duration,
+}
+
+impl Powers{
+    pub fn tick(&mut self){
+        self.shield = self.shield.saturating_sub(TIMESTEP);
+        self.spread = self.spread.saturating_sub(TIMESTEP);
+        self.rapid = self.rapid.saturating_sub(TIMESTEP);
+    }
+
+    /* Whatever is still running, for the HUD */
+    pub fn active(&self) -> Vec<(Power, Duration)>{
+        [(Power::Shield, self.shield), (Power::Spread, self.spread), (Power::Rapid, self.rapid)]
+            .into_iter()
+            .filter(|(_, left)| !left.is_zero())
+            .collect()
+    }
+
+    /* Held fire keeps shooting while rapid fire lasts */
+    pub fn rapid(&self) -> bool{
+        !self.rapid.is_zero()
+    }
+
+    /* Rapid fire also halves whatever the weapon makes you wait between shots */
+    pub fn cooldown(&self, cooldown: Duration) -> Duration{
+        if self.rapid() { cooldown / 2 } else { cooldown }
+    }
+
+    /* Heading offsets for one press of fire */
+    pub fn spread(&self) -> &'static [f32]{
+        if self.spread.is_zero() { &[0.0] } else { &[-SPREAD_ANGLE, 0.0, SPREAD_ANGLE] }
+    }
+}
+
+/* Takes a hit on the shield if there is one, true when the ship survives */
+pub fn absorb(player: &mut Player) -> bool{
+    if player.powers.shield.is_zero(){
+        return false;
+    }
+    player.powers.shield = Duration::ZERO;
+    player.invulnerable = SHIELD_GRACE;
+    true
+}
+
+/* Rolled for every rock that gets shot down, the drop carries on along the rock's drift */
+pub fn roll_drop(rng: &mut Rng, asteroid: &Asteroid, pickups: &mut Vec<Pickup>){
+    if rng.range(0.0, 1.0) >= DROP_CHANCE{
+        return;
+    }
+
+    let total = DROP_WEIGHTS.iter().map(|&(_, weight)| weight).sum();
+    let mut roll = rng.below(total);
+    let mut power = Power::Shield;
+    for &(candidate, weight) in &DROP_WEIGHTS{
+        if roll < weight{
+            power = candidate;
+            break;
+        }
+        roll -= weight;
+    }
+
+    pickups.push(Pickup{
+        power,
+        position: asteroid.position,
+        previous_position: asteroid.position,
+        velocity: asteroid.velocity,
+        life: PICKUP_LIFE,
+    });
+}
+
+fn collect(world: &mut World, owner: usize, power: Power, position: Point2){
+    let powers = &mut world.players[owner].powers;
+    match power{
+        Power::Shield => powers.shield = SHIELD_TIME,
+        Power::Spread => powers.spread = SPREAD_TIME,
+        Power::Rapid => powers.rapid = RAPID_TIME,
+        Power::Bomb => {
+            /* Every rock on the collector's screen counts as shot, but nothing breaks off.
+             * In a large world the rest of the field is left alone. */
+            let view = view_of(world, owner, 1.0, playfield());
+            let (cleared, kept): (Vec<Asteroid>, Vec<Asteroid>) = std::mem::take(&mut world.asteroid)
+                .into_iter()
+                .partition(|asteroid| !view.images(asteroid.position, asteroid.size / 2.0).is_empty());
+            world.asteroid = kept;
+            world.players[owner].score += cleared.len() as u32;
+            world.effects.push(Effect::Bombed(cleared));
+            world.calm = BOMB_CALM;
+        }
+    }
+    world.effects.push(Effect::Collected(power, position));
+}
+
+/* Drifts and ages the pickups, then hands each one to the first ship touching it */
+pub fn pickup_update(world: &mut World){
+    let win = world.field;
+    world.calm = world.calm.saturating_sub(TIMESTEP);
+
+    for pickup in &mut world.pickups{
+        pickup.previous_position = pickup.position;
+        pickup.position += pickup.velocity;
+        pickup.position = wrap_point(pickup.position, win);
+        pickup.life = pickup.life.saturating_sub(TIMESTEP);
+    }
+
+    let mut collected = Vec::new();
+    for (i, pickup) in world.pickups.iter().enumerate(){
+        let reach = PICKUP_RADIUS + SPACESHIP_PEAK;
+        let owner = world.players.iter()
+            .position(|player| player.alive && wrapped_offset(player.position - pickup.position, win).length() < reach);
+        if let Some(owner) = owner{
+            collected.push((i, owner, pickup.power, pickup.position));
+        }
+    }
+
+    let mut index = 0;
+    world.pickups.retain(|pickup| {
+        let keep = !pickup.life.is_zero() && !collected.iter().any(|&(i, _, _, _)| i == index);
+        index += 1;
+        keep
+    });
+
+    for (_, owner, power, position) in collected{
+        collect(world, owner, power, position);
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use crate::difficulty::{Difficulty, Preset, Presets};
+    use crate::{generate_asteroid, ASTEROID_MIN_SIZE};
+
+    /* In a large world the bomb only reaches as far as the collector can see */
+    #[test]
+    fn bomb_clears_only_the_screen(){
+        let mut world = World::new(Difficulty::new(Presets::load(), Preset::Normal, None), 1);
+        world.set_large(true);
+        world.restart(1, 1);
+        let ship = world.players[0].position;
+        let far = ship + vec2(playfield().w(), 0.0);
+        world.asteroid = [ship + vec2(50.0, 0.0), far]
+            .into_iter()
+            .map(|position| generate_asteroid(&mut world.rng, position, 8, ASTEROID_MIN_SIZE, ASTEROID_MIN_SIZE, 0.0, false))
+            .collect();
+
+        collect(&mut world, 0, Power::Bomb, ship);
+
+        assert_eq!(world.asteroid.len(), 1);
+        assert_eq!(world.asteroid[0].position, far);
+        assert_eq!(world.players[0].score, 1);
+    }
+}
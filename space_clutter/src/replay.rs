@@ -0,0 +1,466 @@
+use std::path::{Path, PathBuf};
+
+use crate::difficulty::{Difficulty, Preset, Presets};
+use crate::wave::GameMode;
+use crate::{world_step, Input, World, INPUT_BYTES, MAX_PLAYERS};
+
+const REPLAY_DIR: &str = "replays";
+const REPLAY_MAGIC: [u8; 4] = *b"SCRP";
+const REPLAY_FORMAT: u16 = 4;
+/* Frame-step and speed controls work through these, 1x sits in the middle */
+const REPLAY_SPEEDS: [f32; 6] = [0.25, 0.5, 1.0, 2.0, 4.0, 8.0];
+const REPLAY_NORMAL_SPEED: usize = 2;
+
+/* Everything picked before the first step that decides how a game plays out */
+#[derive(Clone)]
+pub struct Setup{
+    pub seed: u64,
+    pub players: usize,
+    pub mode: GameMode,
+    pub preset: Preset,
+    pub friendly_fire: bool,
+    pub large: bool,
+    pub deaths: u32,
+    /* The rules tables it was played under, see `tables_hash` */
+    pub tables: u64,
+}
+
+/* One game as a seed plus the inputs for every step. The scores and the step the
+ * last ship went down are kept too, so playing it back can check they still match. */
+pub struct Replay{
+    pub version: String,
+    pub setup: Setup,
+    pub inputs: Vec<Vec<Input>>,
+    pub scores: Vec<u32>,
+    pub ended: Option<u32>,
+}
+
+/* A replay being watched in the window */
+pub struct Playback{
+    pub replay: Replay,
+    pub paused: bool,
+    pub step: bool,
+    speed: usize,
+}
+
+/* Reads a replay file front to back, failing rather than panicking when it runs short */
+struct Reader<'a>{
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a>{
+    fn take(&mut self, count: usize) -> Result<&'a [u8], String>{
+        if self.bytes.len() < count{
+            return Err("file is cut short".to_string());
+        }
+        let (taken, rest) = self.bytes.split_at(count);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    fn u8(&mut self) -> Result<u8, String>{
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, String>{
+        let bytes = self.take(2)?;
+        Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn u32(&mut self) -> Result<u32, String>{
+        let bytes = self.take(4)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn u64(&mut self) -> Result<u64, String>{
+        let bytes = self.take(8)?;
+        let mut value = [0u8; 8];
+        value.copy_from_slice(bytes);
+        Ok(u64::from_le_bytes(value))
+    }
+}
+
+pub(crate) fn mode_to_byte(mode: GameMode) -> u8{
+    match mode{
+        GameMode::Endless => 0,
+        GameMode::Waves => 1,
+    }
+}
+
+fn mode_from_byte(byte: u8) -> Result<GameMode, String>{
+    match byte{
+        0 => Ok(GameMode::Endless),
+        1 => Ok(GameMode::Waves),
+        _ => Err(format!("unknown game mode {}", byte)),
+    }
+}
+
+pub(crate) fn preset_to_byte(preset: Preset) -> u8{
+    match preset{
+        Preset::Easy => 0,
+        Preset::Normal => 1,
+        Preset::Arcade => 2,
+    }
+}
+
+fn preset_from_byte(byte: u8) -> Result<Preset, String>{
+    match byte{
+        0 => Ok(Preset::Easy),
+        1 => Ok(Preset::Normal),
+        2 => Ok(Preset::Arcade),
+        _ => Err(format!("unknown difficulty preset {}", byte)),
+    }
+}
+
+/* FNV-1a over every rules table the world loaded. A replay recorded under other
+ * weapons, handling, rocks, hazards or difficulty would play out a different game. */
+pub fn tables_hash(world: &World) -> u64{
+    let tables = [
+        toml::to_string(&world.arsenal),
+        toml::to_string(&world.handling),
+        toml::to_string(&world.rocks),
+        toml::to_string(&world.hazards),
+        toml::to_string(world.difficulty.presets()),
+    ];
+    let mut hash: u64 = 0xCBF2_9CE4_8422_2325;
+    for table in tables{
+        for byte in table.expect("Loaded tables always serialise").bytes(){
+            hash = (hash ^ byte as u64).wrapping_mul(0x0100_0000_01B3);
+        }
+    }
+    hash
+}
+
+/* What the world was set up with, read straight after a restart */
+pub fn setup_of(world: &World) -> Setup{
+    Setup{
+        seed: world.seed,
+        players: world.players.len(),
+        mode: world.mode,
+        preset: world.difficulty.preset,
+        friendly_fire: world.friendly_fire,
+        large: world.large,
+        deaths: world.difficulty.deaths(),
+        tables: tables_hash(world),
+    }
+}
+
+/* Restarts the world exactly as the recorded game started */
+pub fn apply(setup: &Setup, world: &mut World){
+    world.mode = setup.mode;
+    world.friendly_fire = setup.friendly_fire;
+    world.set_large(setup.large);
+    world.difficulty.set_preset(setup.preset);
+    world.difficulty.set_deaths(setup.deaths);
+    world.restart(setup.players, setup.seed);
+}
+
+impl Replay{
+    pub fn new(setup: Setup) -> Replay{
+        Replay{
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            scores: vec![0; setup.players],
+            setup,
+            inputs: Vec::new(),
+            ended: None,
+        }
+    }
+
+    pub fn record(&mut self, inputs: &[Input]){
+        let mut row = inputs.to_vec();
+        row.resize(self.setup.players, Input::default());
+        self.inputs.push(row);
+    }
+
+    pub fn finish(&mut self, world: &World){
+        self.scores = world.players.iter().map(|player| player.score).collect();
+    }
+
+    /* Inputs for one step, nobody touches anything once the recording runs out */
+    pub fn inputs(&self, tick: u32) -> Vec<Input>{
+        self.inputs.get(tick as usize)
+            .cloned()
+            .unwrap_or_else(|| vec![Input::default(); self.setup.players])
+    }
+
+    /* Header, then the inputs as runs of identical rows since most steps repeat the last */
+    fn encode(&self) -> Vec<u8>{
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&REPLAY_MAGIC);
+        bytes.extend_from_slice(&REPLAY_FORMAT.to_le_bytes());
+        bytes.push(self.version.len() as u8);
+        bytes.extend_from_slice(self.version.as_bytes());
+        bytes.extend_from_slice(&self.setup.seed.to_le_bytes());
+        bytes.push(self.setup.players as u8);
+        bytes.push(mode_to_byte(self.setup.mode));
+        bytes.push(preset_to_byte(self.setup.preset));
+        bytes.push(self.setup.friendly_fire as u8);
+        bytes.push(self.setup.large as u8);
+        bytes.extend_from_slice(&self.setup.deaths.to_le_bytes());
+        bytes.extend_from_slice(&self.setup.tables.to_le_bytes());
+        bytes.extend_from_slice(&self.ended.unwrap_or(u32::MAX).to_le_bytes());
+        for score in &self.scores{
+            bytes.extend_from_slice(&score.to_le_bytes());
+        }
+
+        bytes.extend_from_slice(&(self.inputs.len() as u32).to_le_bytes());
+        let mut rows = self.inputs.iter().peekable();
+        while let Some(row) = rows.next(){
+            let mut run: u16 = 1;
+            while run < u16::MAX && rows.peek() == Some(&row){
+                rows.next();
+                run += 1;
+            }
+            bytes.extend_from_slice(&run.to_le_bytes());
+            bytes.extend(row.iter().flat_map(|input| input.to_bytes()));
+        }
+        bytes
+    }
+
+    fn decode(bytes: &[u8]) -> Result<Replay, String>{
+        let mut reader = Reader{ bytes };
+        if reader.take(4)? != REPLAY_MAGIC{
+            return Err("not a replay file".to_string());
+        }
+        let format = reader.u16()?;
+        if format != REPLAY_FORMAT{
+            return Err(format!("replay format {} is not supported", format));
+        }
+
+        let length = reader.u8()? as usize;
+        let version = String::from_utf8_lossy(reader.take(length)?).into_owned();
+        let seed = reader.u64()?;
+        let players = reader.u8()? as usize;
+        if !(1..=MAX_PLAYERS).contains(&players){
+            return Err(format!("{} players is not a game", players));
+        }
+        let mode = mode_from_byte(reader.u8()?)?;
+        let preset = preset_from_byte(reader.u8()?)?;
+        let friendly_fire = reader.u8()? != 0;
+        let large = reader.u8()? != 0;
+        let deaths = reader.u32()?;
+        let tables = reader.u64()?;
+        let ended = Some(reader.u32()?).filter(|&tick| tick != u32::MAX);
+        let scores = (0..players).map(|_| reader.u32()).collect::<Result<Vec<u32>, String>>()?;
+
+        let ticks = reader.u32()? as usize;
+        let mut inputs = Vec::with_capacity(ticks);
+        while inputs.len() < ticks{
+            let run = reader.u16()? as usize;
+            let row: Vec<Input> = reader.take(players * INPUT_BYTES)?.chunks_exact(INPUT_BYTES).map(Input::from_bytes).collect();
+            inputs.extend(std::iter::repeat_n(row, run));
+        }
+        inputs.truncate(ticks);
+
+        Ok(Replay{
+            version,
+            setup: Setup{ seed, players, mode, preset, friendly_fire, large, deaths, tables },
+            inputs,
+            scores,
+            ended,
+        })
+    }
+
+    pub fn save(&self) -> std::io::Result<PathBuf>{
+        std::fs::create_dir_all(REPLAY_DIR)?;
+        let stamp = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_secs())
+            .unwrap_or(0);
+        let path = Path::new(REPLAY_DIR).join(format!("{}-{}.replay", stamp, self.setup.seed));
+        std::fs::write(&path, self.encode())?;
+        Ok(path)
+    }
+
+    pub fn load(path: &Path) -> Result<Replay, String>{
+        let bytes = std::fs::read(path).map_err(|error| error.to_string())?;
+        let replay = Replay::decode(&bytes)?;
+        if replay.version != env!("CARGO_PKG_VERSION"){
+            log::warn!("Replay {} was recorded on version {}, it may not play back the same", path.display(), replay.version);
+        }
+        Ok(replay)
+    }
+}
+
+impl Playback{
+    pub fn new(replay: Replay) -> Playback{
+        Playback{
+            replay,
+            paused: false,
+            step: false,
+            speed: REPLAY_NORMAL_SPEED,
+        }
+    }
+
+    pub fn speed(&self) -> f32{
+        REPLAY_SPEEDS[self.speed]
+    }
+
+    pub fn faster(&mut self){
+        self.speed = (self.speed + 1).min(REPLAY_SPEEDS.len() - 1);
+    }
+
+    pub fn slower(&mut self){
+        self.speed = self.speed.saturating_sub(1);
+    }
+}
+
+/* Replays only play back under the tables they were recorded with */
+pub fn playable(replay: &Replay, world: &World) -> Result<(), String>{
+    if replay.setup.tables != tables_hash(world){
+        return Err("recorded with different weapons, handling, rocks, hazards or difficulty tables".to_string());
+    }
+    Ok(())
+}
+
+/* `--replay FILE` watches a recorded game instead of playing */
+pub fn playback_from_args(world: &World) -> Option<Playback>{
+    let args: Vec<String> = std::env::args().collect();
+    let position = args.iter().position(|arg| arg == "--replay")?;
+    let path = args.get(position + 1)?;
+    match Replay::load(Path::new(path)).and_then(|replay| playable(&replay, world).map(|_| replay)){
+        Ok(replay) => Some(Playback::new(replay)),
+        Err(error) => {
+            log::error!("Could not load replay {}: {}", path, error);
+            None
+        }
+    }
+}
+
+/* Steps a replay through the bare world and checks it ends the way it was recorded */
+pub fn check(replay: &Replay) -> Result<(), String>{
+    let mut world = World::new(Difficulty::new(Presets::load(), replay.setup.preset, None), replay.setup.seed);
+    playable(replay, &world)?;
+    apply(&replay.setup, &mut world);
+    let mut ended = None;
+    for inputs in &replay.inputs{
+        world_step(&mut world, inputs);
+        if ended.is_none() && world.all_crashed(){
+            ended = Some(world.frame);
+        }
+    }
+
+    let scores: Vec<u32> = world.players.iter().map(|player| player.score).collect();
+    if scores != replay.scores{
+        return Err(format!("scores {:?}, recorded {:?}", scores, replay.scores));
+    }
+    if ended != replay.ended{
+        return Err(format!("game ended on tick {:?}, recorded {:?}", ended, replay.ended));
+    }
+    Ok(())
+}
+
+/* Headless regression run over replay files, `--replay-test FILE...` */
+pub fn run_replay_tests() -> bool{
+    let paths: Vec<String> = std::env::args()
+        .skip_while(|arg| arg != "--replay-test")
+        .skip(1)
+        .collect();
+    if paths.is_empty(){
+        println!("--replay-test needs at least one replay file");
+        return false;
+    }
+
+    let mut passed = true;
+    for path in &paths{
+        match Replay::load(Path::new(path)).and_then(|replay| check(&replay)){
+            Ok(()) => println!("ok      {}", path),
+            Err(error) => {
+                println!("FAILED  {}: {}", path, error);
+                passed = false;
+            }
+        }
+    }
+    passed
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use crate::pilot::Pilot;
+
+    /* Long enough for rocks to break, ships to die and a wave or two to pass */
+    const TEST_TICKS: u32 = 3600;
+
+    fn new_world(setup: &Setup) -> World{
+        let mut world = World::new(Difficulty::new(Presets::load(), setup.preset, None), setup.seed);
+        apply(setup, &mut world);
+        world
+    }
+
+    /* A game flown by pilots and recorded the way the window records one */
+    fn recorded(setup: Setup) -> Replay{
+        let mut world = new_world(&setup);
+        let mut pilots: Vec<Pilot> = (0..setup.players).map(|_| Pilot::new()).collect();
+        let mut replay = Replay::new(setup);
+        while world.frame < TEST_TICKS && replay.ended.is_none(){
+            let inputs: Vec<Input> = pilots.iter_mut().enumerate().map(|(i, pilot)| pilot.fly(&world, i)).collect();
+            replay.record(&inputs);
+            world_step(&mut world, &inputs);
+            if world.all_crashed(){
+                replay.ended = Some(world.frame);
+            }
+        }
+        replay.finish(&world);
+        replay
+    }
+
+    fn setup(players: usize, mode: GameMode, large: bool) -> Setup{
+        let tables = tables_hash(&World::new(Difficulty::new(Presets::load(), Preset::Normal, None), 1234));
+        Setup{ seed: 1234, players, mode, preset: Preset::Normal, friendly_fire: players > 1, large, deaths: 0, tables }
+    }
+
+    #[test]
+    fn recordings_play_back_the_same(){
+        for setup in [setup(1, GameMode::Endless, false), setup(2, GameMode::Waves, false), setup(4, GameMode::Waves, true)]{
+            let replay = recorded(setup);
+            let decoded = Replay::decode(&replay.encode()).unwrap();
+            assert_eq!(decoded.inputs.len(), replay.inputs.len());
+            check(&decoded).unwrap();
+        }
+    }
+
+    /* Games saved by an earlier build have to end the same in this one. When the
+     * rules tables change on purpose they need recording again. */
+    #[test]
+    fn saved_replays_still_play_back(){
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/replays");
+        let mut count = 0;
+        for entry in std::fs::read_dir(&dir).expect("No saved replays"){
+            let path = entry.unwrap().path();
+            let replay = Replay::load(&path).unwrap();
+            if let Err(error) = check(&replay){
+                panic!("{}: {}", path.display(), error);
+            }
+            count += 1;
+        }
+        assert!(count > 0, "No saved replays in {}", dir.display());
+    }
+
+    #[test]
+    fn tampered_scores_are_caught(){
+        let mut replay = recorded(setup(1, GameMode::Endless, false));
+        replay.scores[0] += 1;
+        assert!(check(&replay).is_err());
+    }
+
+    #[test]
+    fn other_tables_are_refused(){
+        let replay = Replay::new(setup(1, GameMode::Endless, false));
+        let mut world = new_world(&replay.setup);
+        assert!(playable(&replay, &world).is_ok());
+        world.handling.drag += 0.01;
+        assert!(playable(&replay, &world).is_err());
+    }
+
+    #[test]
+    fn other_formats_are_refused(){
+        let bytes = Replay::new(setup(2, GameMode::Waves, false)).encode();
+        assert!(Replay::decode(b"NOPE").is_err());
+        assert!(Replay::decode(&bytes[..bytes.len() - 1]).is_err());
+
+        let mut future = bytes.clone();
+        future[4..6].copy_from_slice(&(REPLAY_FORMAT + 1).to_le_bytes());
+        assert!(Replay::decode(&future).is_err());
+    }
+}
@@ -0,0 +1,64 @@
+/* Seeded generator for every roll the simulation makes. SplitMix64, the whole
+ * state is one u64 so world snapshots copy and hash it for free. */
+#[derive(Copy,Clone)]
+pub struct Rng{
+    state: u64,
+}
+
+impl Rng{
+    pub fn new(seed: u64) -> Rng{
+        Rng{ state: seed }
+    }
+
+    pub fn state(&self) -> u64{
+        self.state
+    }
+
+    pub fn next_u64(&mut self) -> u64{
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /* Uniform in [min, max), built from the top 24 bits so every step is exact */
+    pub fn range(&mut self, min: f32, max: f32) -> f32{
+        let unit = (self.next_u64() >> 40) as f32 / (1u32 << 24) as f32;
+        min + (max - min) * unit
+    }
+
+    /* Uniform in [0, n) */
+    pub fn below(&mut self, n: u32) -> u32{
+        (self.next_u64() % n as u64) as u32
+    }
+}
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+fn since_epoch() -> std::time::Duration{
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+}
+
+/* Fresh seed for a local game, kept to 32 bits so it's short enough to type back in */
+pub fn time_seed() -> u64{
+    Rng::new(since_epoch().as_nanos() as u64).next_u64() & 0xFFFF_FFFF
+}
+
+/* `--seed N` plays every game from seed N, `--seed daily` from one that changes at midnight UTC */
+pub fn seed_from_args() -> Option<u64>{
+    let args: Vec<String> = std::env::args().collect();
+    let position = args.iter().position(|arg| arg == "--seed")?;
+    let seed = match args.get(position + 1).map(String::as_str){
+        Some("daily") => Some(since_epoch().as_secs() / SECONDS_PER_DAY),
+        Some(seed) => seed.parse().ok(),
+        None => None,
+    };
+
+    if seed.is_none(){
+        log::error!("--seed needs a number or daily");
+    }
+    seed
+}
@@ -0,0 +1,141 @@
+use nannou::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::config;
+use crate::spatial::{wrapped_delta, SpatialHash};
+use crate::Asteroid;
+
+const ROCKS_PATH: &str = "assets/rocks.toml";
+const DEFAULT_ROCKS: &str = include_str!("../assets/rocks.toml");
+
+#[derive(Deserialize,Serialize,Clone)]
+pub struct RockPhysics{
+    pub enabled: bool,
+    pub restitution: f32,
+    pub crack: bool,
+    pub crack_speed: f32,
+}
+
+impl RockPhysics{
+    pub fn load() -> RockPhysics{
+        config::load(ROCKS_PATH, DEFAULT_ROCKS)
+    }
+}
+
+/* Rocks are flat discs of even density so mass goes with area */
+fn mass(asteroid: &Asteroid) -> f32{
+    asteroid.size * asteroid.size
+}
+
+/* Bounces overlapping rocks off each other and pushes them apart. Returns the
+ * indices of rocks hit hard enough to crack, smallest of each pair only. */
+pub fn collide_asteroids(asteroids: &mut [Asteroid], grid: &SpatialHash, physics: &RockPhysics, win: Rect) -> Vec<usize>{
+    let mut pairs = Vec::new();
+    let mut cracked = Vec::new();
+    grid.pairs(&mut pairs);
+
+    for (a, b) in pairs{
+        let delta = asteroids[b].position - asteroids[a].position;
+        let delta = vec2(wrapped_delta(delta.x, win.w()), wrapped_delta(delta.y, win.h()));
+        let reach = (asteroids[a].size + asteroids[b].size) / 2.0;
+        let distance = delta.length();
+        if distance >= reach || distance == 0.0{
+            continue;
+        }
+
+        let normal = delta / distance;
+        let inv_a = 1.0 / mass(&asteroids[a]);
+        let inv_b = 1.0 / mass(&asteroids[b]);
+
+        /* Share the overlap out so the lighter rock moves further */
+        let overlap = reach - distance;
+        asteroids[a].position -= normal * overlap * inv_a / (inv_a + inv_b);
+        asteroids[b].position += normal * overlap * inv_b / (inv_a + inv_b);
+
+        let closing = (asteroids[b].velocity - asteroids[a].velocity).dot(normal);
+        if closing >= 0.0{
+            continue;
+        }
+
+        let impulse = -(1.0 + physics.restitution) * closing / (inv_a + inv_b);
+        asteroids[a].velocity -= normal * impulse * inv_a;
+        asteroids[b].velocity += normal * impulse * inv_b;
+
+        if physics.crack && -closing > physics.crack_speed{
+            let smaller = if asteroids[a].size < asteroids[b].size { a } else { b };
+            if !cracked.contains(&smaller){
+                cracked.push(smaller);
+            }
+        }
+    }
+
+    cracked
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use crate::rng::Rng;
+    use crate::spatial::wrap_point;
+    use crate::{build_broadphase, generate_asteroid, playfield, BROADPHASE_CELL_SIZE};
+
+    const ELASTIC: RockPhysics = RockPhysics{ enabled: true, restitution: 1.0, crack: true, crack_speed: 5.0 };
+
+    /* Two rocks overlapping head on, the big one going right and the small one left */
+    fn head_on(win: Rect, at: f32) -> Vec<Asteroid>{
+        let mut rng = Rng::new(1);
+        let mut rocks: Vec<Asteroid> = [(at - 10.0, 40.0, 3.0), (at + 10.0, 30.0, -4.0)]
+            .into_iter()
+            .map(|(x, size, speed)| {
+                let mut rock = generate_asteroid(&mut rng, pt2(x, win.y()), 8, size, size, 0.0, false);
+                rock.velocity = vec2(speed, 0.0);
+                rock
+            })
+            .collect();
+        for rock in &mut rocks{
+            rock.position = wrap_point(rock.position, win);
+        }
+        rocks
+    }
+
+    fn collide(rocks: &mut [Asteroid], win: Rect) -> Vec<usize>{
+        let mut grid = SpatialHash::new(win, BROADPHASE_CELL_SIZE);
+        build_broadphase(&mut grid, rocks);
+        collide_asteroids(rocks, &grid, &ELASTIC, win)
+    }
+
+    fn momentum(rocks: &[Asteroid]) -> Vec2{
+        rocks.iter().fold(Vec2::ZERO, |total, rock| total + rock.velocity * mass(rock))
+    }
+
+    fn energy(rocks: &[Asteroid]) -> f32{
+        rocks.iter().map(|rock| mass(rock) * rock.velocity.length_squared() / 2.0).sum()
+    }
+
+    #[test]
+    fn elastic_bounces_keep_momentum_and_energy(){
+        let win = playfield();
+        let mut rocks = head_on(win, 0.0);
+        let (before, kinetic) = (momentum(&rocks), energy(&rocks));
+
+        let cracked = collide(&mut rocks, win);
+
+        assert!((momentum(&rocks) - before).length() < before.length().max(1.0) * 1e-4);
+        assert!((energy(&rocks) - kinetic).abs() < kinetic * 1e-4);
+        /* Now moving apart, and no longer overlapping */
+        assert!(rocks[0].velocity.x < rocks[1].velocity.x);
+        assert!(rocks[0].position.distance(rocks[1].position) >= (rocks[0].size + rocks[1].size) / 2.0 - 1e-3);
+        /* Closing at 7 is over the crack speed, the smaller rock takes it */
+        assert_eq!(cracked, vec![1]);
+    }
+
+    #[test]
+    fn rocks_bounce_across_the_seam(){
+        let win = playfield();
+        let mut rocks = head_on(win, win.right());
+        let before = momentum(&rocks);
+        collide(&mut rocks, win);
+        assert!((momentum(&rocks) - before).length() < before.length().max(1.0) * 1e-4);
+        assert!(rocks[0].velocity.x < 0.0);
+    }
+}
@@ -0,0 +1,185 @@
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+use crate::config;
+
+/* Bumped whenever an entry changes shape, older files are set aside rather than misread */
+const SCORES_FORMAT: u32 = 1;
+const SCORES_FILE: &str = "scores.toml";
+pub const TABLE_SIZE: usize = 10;
+pub const INITIALS_LENGTH: usize = 3;
+
+#[derive(Serialize,Deserialize,Clone)]
+pub struct Entry{
+    pub initials: String,
+    pub score: u32,
+    /* Names as shown on the menu, kept as text so the file reads on its own */
+    pub mode: String,
+    pub preset: String,
+}
+
+#[derive(Serialize,Deserialize,Clone,Default)]
+pub struct HighScores{
+    version: u32,
+    #[serde(rename = "entry", default)]
+    pub entries: Vec<Entry>,
+}
+
+pub fn path() -> PathBuf{
+    config::data_dir().join(SCORES_FILE)
+}
+
+/* Three capitals, whatever a hand-edited file says */
+fn clean_initials(initials: &str) -> String{
+    let mut clean: String = initials.chars()
+        .filter(|c| c.is_ascii_alphabetic() || *c == ' ')
+        .map(|c| c.to_ascii_uppercase())
+        .take(INITIALS_LENGTH)
+        .collect();
+    while clean.len() < INITIALS_LENGTH{
+        clean.push(' ');
+    }
+    clean
+}
+
+/* Moves a file we can't use out of the way, so the next save doesn't write over it */
+fn set_aside(path: &Path, reason: &str){
+    let aside = path.with_extension("bad");
+    log::warn!("Could not use {}: {}, moved to {} and starting a new table", path.display(), reason, aside.display());
+    std::fs::rename(path, &aside).ok();
+}
+
+impl HighScores{
+    pub fn load() -> HighScores{
+        HighScores::load_from(&path())
+    }
+
+    pub fn save(&self) -> std::io::Result<PathBuf>{
+        let path = path();
+        self.save_to(&path)?;
+        Ok(path)
+    }
+
+    /* Anything wrong with the file leaves an empty table, the game plays on regardless */
+    fn load_from(path: &Path) -> HighScores{
+        let bytes = match std::fs::read(path){
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return HighScores::default(),
+            Err(e) => {
+                log::warn!("Could not read {}: {}", path.display(), e);
+                return HighScores::default();
+            }
+        };
+
+        let text = match String::from_utf8(bytes){
+            Ok(text) => text,
+            Err(_) => {
+                set_aside(path, "not text");
+                return HighScores::default();
+            }
+        };
+        let mut table: HighScores = match toml::from_str(&text){
+            Ok(table) => table,
+            Err(e) => {
+                set_aside(path, &e.to_string());
+                return HighScores::default();
+            }
+        };
+        if table.version != SCORES_FORMAT{
+            set_aside(path, &format!("format {} is not supported", table.version));
+            return HighScores::default();
+        }
+
+        for entry in &mut table.entries{
+            entry.initials = clean_initials(&entry.initials);
+        }
+        table.entries.sort_by_key(|entry| std::cmp::Reverse(entry.score));
+        table.entries.truncate(TABLE_SIZE);
+        table
+    }
+
+    fn save_to(&self, path: &Path) -> std::io::Result<()>{
+        config::save(path, &HighScores{ version: SCORES_FORMAT, entries: self.entries.clone() })
+    }
+
+    /* Nothing scores a place for zero, and ties go to whoever got there first */
+    pub fn qualifies(&self, score: u32) -> bool{
+        score > 0 && (self.entries.len() < TABLE_SIZE || self.entries.iter().any(|entry| score > entry.score))
+    }
+
+    /* Slots the entry in below any equal scores, returns its place from 0 */
+    pub fn insert(&mut self, entry: Entry) -> Option<usize>{
+        let place = self.entries.iter().position(|other| entry.score > other.score).unwrap_or(self.entries.len());
+        if place >= TABLE_SIZE{
+            return None;
+        }
+        self.entries.insert(place, Entry{ initials: clean_initials(&entry.initials), ..entry });
+        self.entries.truncate(TABLE_SIZE);
+        Some(place)
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    fn entry(initials: &str, score: u32) -> Entry{
+        Entry{ initials: initials.to_string(), score, mode: "ENDLESS".to_string(), preset: "NORMAL".to_string() }
+    }
+
+    /* A folder of its own for each test, they run side by side */
+    fn scratch(name: &str) -> PathBuf{
+        let dir = std::env::temp_dir().join(format!("space_clutter_scores_{}_{}", std::process::id(), name));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn missing_file_is_an_empty_table(){
+        let dir = scratch("missing");
+        assert!(HighScores::load_from(&dir.join(SCORES_FILE)).entries.is_empty());
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn bad_files_are_set_aside(){
+        let dir = scratch("bad");
+        let path = dir.join(SCORES_FILE);
+        for (name, bytes) in [("garbage", &b"\xff\xfe not a table ]]"[..]), ("bad toml", b"version = 1\n[[entry]\n"), ("future format", b"version = 99\n")]{
+            std::fs::write(&path, bytes).unwrap();
+            assert!(HighScores::load_from(&path).entries.is_empty(), "{} file gave scores", name);
+            assert!(!path.exists() && path.with_extension("bad").exists(), "{} file was not set aside", name);
+        }
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn ties_with_last_place_miss_out(){
+        let mut table = HighScores::default();
+        for score in 1..=TABLE_SIZE as u32{
+            table.insert(entry("ABC", score * 10));
+        }
+        assert!(table.insert(entry("TIE", 10)).is_none());
+        assert!(!table.qualifies(10));
+        assert!(table.qualifies(11));
+        assert!(!table.qualifies(0));
+    }
+
+    #[test]
+    fn round_trip_keeps_the_top_ten(){
+        let dir = scratch("round_trip");
+        let path = dir.join(SCORES_FILE);
+        let mut table = HighScores::default();
+        for score in 1..=TABLE_SIZE as u32 + 2{
+            table.insert(entry("abcd", score * 10));
+        }
+        table.save_to(&path).unwrap();
+
+        let loaded = HighScores::load_from(&path);
+        let scores: Vec<u32> = loaded.entries.iter().map(|entry| entry.score).collect();
+        let expected: Vec<u32> = (3..=TABLE_SIZE as u32 + 2).rev().map(|score| score * 10).collect();
+        assert_eq!(scores, expected);
+        assert!(loaded.entries.iter().all(|entry| entry.initials == "ABC"));
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
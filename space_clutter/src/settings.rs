@@ -0,0 +1,152 @@
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+use space_clutter::config;
+use space_clutter::difficulty::Preset;
+
+use crate::controls::{Action, Bindings, SHIP_ACTIONS};
+use crate::menu::{Row, Widget};
+
+const SETTINGS_FILE: &str = "settings.toml";
+/* Sliders move this far for each press of Left or Right */
+const VOLUME_STEP: f32 = 0.1;
+
+/* Missing fields take their defaults, so a file from an older build still loads */
+#[derive(Serialize,Deserialize,Clone,PartialEq)]
+#[serde(default)]
+pub struct Settings{
+    pub master_volume: f32,
+    pub music_volume: f32,
+    pub sfx_volume: f32,
+    pub shake: bool,
+    pub hit_stop: bool,
+    pub flash: bool,
+    pub preset: String,
+}
+
+impl Default for Settings{
+    fn default() -> Settings{
+        Settings{
+            master_volume: 1.0,
+            music_volume: 1.0,
+            sfx_volume: 1.0,
+            shake: true,
+            hit_stop: true,
+            flash: true,
+            preset: Preset::Normal.name().to_string(),
+        }
+    }
+}
+
+fn path() -> PathBuf{
+    config::data_dir().join(SETTINGS_FILE)
+}
+
+/* What each row of the options menu changes */
+#[derive(Copy,Clone,PartialEq)]
+pub enum Item{
+    MasterVolume,
+    MusicVolume,
+    SfxVolume,
+    Preset,
+    Shake,
+    HitStop,
+    Flash,
+    Bind(usize, Action),
+    Back,
+}
+
+impl Settings{
+    /* A file that won't parse leaves the defaults, the game plays on regardless */
+    pub fn load() -> Settings{
+        let path = path();
+        let text = match std::fs::read_to_string(&path){
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Settings::default(),
+            Err(e) => {
+                log::warn!("Could not read {}: {}, using default settings", path.display(), e);
+                return Settings::default();
+            }
+        };
+
+        let mut settings: Settings = match toml::from_str(&text){
+            Ok(settings) => settings,
+            Err(e) => {
+                log::warn!("Could not read {}: {}, using default settings", path.display(), e);
+                return Settings::default();
+            }
+        };
+
+        /* Clamp anything a hand edit put out of range */
+        for volume in [&mut settings.master_volume, &mut settings.music_volume, &mut settings.sfx_volume]{
+            *volume = volume.clamp(0.0, 1.0);
+        }
+        if Preset::from_name(&settings.preset).is_none(){
+            settings.preset = Settings::default().preset;
+        }
+        settings
+    }
+
+    pub fn save(&self){
+        let path = path();
+        match config::save(&path, self){
+            Ok(()) => log::info!("Saved settings {}", path.display()),
+            Err(error) => log::warn!("Could not save settings: {}", error),
+        }
+    }
+
+    pub fn preset(&self) -> Preset{
+        Preset::from_name(&self.preset).unwrap_or(Preset::Normal)
+    }
+
+    /* Key rows for every ship the bindings know about */
+    pub fn items(&self, bindings: &Bindings) -> Vec<Item>{
+        let mut items = vec![
+            Item::MasterVolume,
+            Item::MusicVolume,
+            Item::SfxVolume,
+            Item::Preset,
+            Item::Shake,
+            Item::HitStop,
+            Item::Flash,
+        ];
+        for player in 0..bindings.players.len(){
+            items.extend(SHIP_ACTIONS.iter().map(|&action| Item::Bind(player, action)));
+        }
+        items.push(Item::Back);
+        items
+    }
+
+    pub fn row(&self, bindings: &Bindings, item: Item) -> Row{
+        let (label, widget) = match item{
+            Item::MasterVolume => ("MASTER VOLUME".to_string(), Widget::Slider(self.master_volume)),
+            Item::MusicVolume => ("MUSIC VOLUME".to_string(), Widget::Slider(self.music_volume)),
+            Item::SfxVolume => ("SFX VOLUME".to_string(), Widget::Slider(self.sfx_volume)),
+            Item::Preset => ("DIFFICULTY".to_string(), Widget::List(self.preset().name().to_string())),
+            Item::Shake => ("SCREEN SHAKE".to_string(), Widget::Toggle(self.shake)),
+            Item::HitStop => ("HIT STOP".to_string(), Widget::Toggle(self.hit_stop)),
+            Item::Flash => ("FLASH".to_string(), Widget::Toggle(self.flash)),
+            Item::Bind(player, action) => return bindings.row(player, action),
+            Item::Back => ("BACK".to_string(), Widget::Button),
+        };
+        Row{ label, widget }
+    }
+
+    /* Left and Right, `step` is -1 or 1. Volumes snap to whole steps so they read back as round numbers. */
+    pub fn adjust(&mut self, item: Item, step: i32){
+        let slide = |volume: &mut f32| *volume = ((*volume / VOLUME_STEP).round() + step as f32).clamp(0.0, 1.0 / VOLUME_STEP) * VOLUME_STEP;
+        match item{
+            Item::MasterVolume => slide(&mut self.master_volume),
+            Item::MusicVolume => slide(&mut self.music_volume),
+            Item::SfxVolume => slide(&mut self.sfx_volume),
+            Item::Preset => {
+                let preset = if step < 0 { self.preset().previous() } else { self.preset().next() };
+                self.preset = preset.name().to_string();
+            }
+            Item::Shake => self.shake ^= true,
+            Item::HitStop => self.hit_stop ^= true,
+            Item::Flash => self.flash ^= true,
+            Item::Bind(..) | Item::Back => {}
+        }
+    }
+}
@@ -0,0 +1,95 @@
+use nannou::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::config;
+use crate::Player;
+
+const HANDLING_PATH: &str = "assets/handling.toml";
+const DEFAULT_HANDLING: &str = include_str!("../assets/handling.toml");
+
+#[derive(Deserialize,Serialize,Clone)]
+pub struct Handling{
+    pub turn_rate: f32,
+    pub thrust: f32,
+    pub drag: f32,
+    pub max_speed: f32,
+}
+
+impl Handling{
+    pub fn load() -> Handling{
+        config::load(HANDLING_PATH, DEFAULT_HANDLING)
+    }
+}
+
+/* Unit vector the nose is pointing along */
+pub fn heading(rotation: f32) -> Vec2{
+    vec2(-rotation.sin(), rotation.cos())
+}
+
+/* Rotation that points the nose along `direction` */
+pub fn rotation_of(direction: Vec2) -> f32{
+    (-direction.x).atan2(direction.y)
+}
+
+/* Signed turn from `from` to `to`, the short way round */
+pub fn angle_between(from: f32, to: f32) -> f32{
+    let turn = (to - from).rem_euclid(TAU);
+    if turn > PI { turn - TAU } else { turn }
+}
+
+/* Semi-implicit Euler, thrust then drag then the speed limit, then move */
+pub fn integrate(player: &mut Player, handling: &Handling, dt: f32){
+    player.rotation += player.rotation_inc * dt;
+
+    if player.thrust{
+        player.velocity += heading(player.rotation) * handling.thrust * dt;
+    }
+
+    player.velocity *= (-handling.drag * dt).exp();
+    player.velocity = player.velocity.clamp_length_max(handling.max_speed);
+    player.position += player.velocity * dt;
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use crate::new_player;
+
+    const HANDLING: Handling = Handling{ turn_rate: 4.0, thrust: 300.0, drag: 0.5, max_speed: 200.0 };
+
+    fn close(a: f32, b: f32) -> bool{
+        (a - b).abs() < 1e-3
+    }
+
+    #[test]
+    fn drag_slows_a_coasting_ship(){
+        let mut player = new_player(pt2(0.0, 0.0), 1);
+        player.velocity = vec2(100.0, 0.0);
+        integrate(&mut player, &HANDLING, 1.0);
+        assert!(close(player.velocity.x, 100.0 * (-0.5f32).exp()));
+        assert!(close(player.position.x, player.velocity.x));
+    }
+
+    #[test]
+    fn thrust_stops_at_the_speed_cap(){
+        let mut player = new_player(pt2(0.0, 0.0), 1);
+        player.thrust = true;
+        for _ in 0..600{
+            integrate(&mut player, &HANDLING, 1.0 / 60.0);
+        }
+        assert!(close(player.velocity.length(), HANDLING.max_speed));
+        /* Nose up at rotation 0 */
+        assert!(player.velocity.y > 0.0 && close(player.velocity.x, 0.0));
+    }
+
+    #[test]
+    fn turns_at_the_given_rate_the_short_way_round(){
+        let mut player = new_player(pt2(0.0, 0.0), 1);
+        player.rotation_inc = HANDLING.turn_rate;
+        integrate(&mut player, &HANDLING, 0.25);
+        assert!(close(player.rotation, 1.0));
+
+        assert!(close(angle_between(0.1, TAU - 0.1), -0.2));
+        assert!(close(rotation_of(heading(1.0)), 1.0));
+    }
+}
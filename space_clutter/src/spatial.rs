@@ -0,0 +1,154 @@
+use nannou::prelude::*;
+
+#[derive(Copy,Clone)]
+struct Entry{
+    id: usize,
+    position: Point2,
+    radius: f32,
+}
+
+/* Uniform grid over the wrapped playfield, rebuilt each step. Every entity lives
+ * in the one cell holding its centre; queries widen their search by the biggest
+ * radius inserted so nothing overlapping a neighbouring cell is missed. Boxes
+ * are compared through the wrap so rocks straddling an edge still pair up. */
+#[derive(Clone)]
+pub struct SpatialHash{
+    cell_size: f32,
+    cols: i32,
+    rows: i32,
+    bounds: Rect,
+    max_radius: f32,
+    cells: Vec<Vec<Entry>>,
+}
+
+pub fn wrapped_delta(delta: f32, span: f32) -> f32{
+    delta - span * (delta / span).round()
+}
+
+/* Shortest way from one point to another across a field that wraps at its edges */
+pub fn wrapped_offset(delta: Vec2, bounds: Rect) -> Vec2{
+    vec2(wrapped_delta(delta.x, bounds.w()), wrapped_delta(delta.y, bounds.h()))
+}
+
+/* The same point brought back inside the field after drifting over an edge */
+pub fn wrap_point(position: Point2, bounds: Rect) -> Point2{
+    bounds.xy() + wrapped_offset(position - bounds.xy(), bounds)
+}
+
+/* Where the window sits over a wrapping world. A one-screen world is looked at
+ * from the middle, a large one from whichever ship the camera follows. */
+#[derive(Copy,Clone)]
+pub struct View{
+    pub centre: Point2,
+    pub field: Rect,
+    pub screen: Rect,
+}
+
+impl View{
+    /* Nearest copy of a world position through the wrap, in screen coordinates */
+    pub fn place(&self, position: Point2) -> Point2{
+        wrapped_offset(position - self.centre, self.field)
+    }
+
+    /* Every copy of something `reach` across that lands on screen, so anything
+     * straddling the wrap shows on both sides */
+    pub fn images(&self, position: Point2, reach: f32) -> Vec<Point2>{
+        let near = self.place(position);
+        let mut images = Vec::new();
+        for dx in [-self.field.w(), 0.0, self.field.w()]{
+            for dy in [-self.field.h(), 0.0, self.field.h()]{
+                let image = near + vec2(dx, dy);
+                let seen = image.x + reach > self.screen.left() && image.x - reach < self.screen.right()
+                    && image.y + reach > self.screen.bottom() && image.y - reach < self.screen.top();
+                if seen{
+                    images.push(image);
+                }
+            }
+        }
+        images
+    }
+}
+
+impl SpatialHash{
+    pub fn new(bounds: Rect, cell_size: f32) -> Self{
+        let cols = (bounds.w() / cell_size).ceil().max(1.0) as i32;
+        let rows = (bounds.h() / cell_size).ceil().max(1.0) as i32;
+        SpatialHash{
+            cell_size,
+            cols,
+            rows,
+            bounds,
+            max_radius: 0.0,
+            cells: (0..cols * rows).map(|_| Vec::new()).collect(),
+        }
+    }
+
+    pub fn clear(&mut self){
+        for cell in &mut self.cells{
+            cell.clear();
+        }
+        self.max_radius = 0.0;
+    }
+
+    fn cell(&self, position: Point2) -> (i32, i32){
+        let x = ((position.x - self.bounds.left()) / self.cell_size).floor() as i32;
+        let y = ((position.y - self.bounds.bottom()) / self.cell_size).floor() as i32;
+        (x, y)
+    }
+
+    fn index(&self, x: i32, y: i32) -> usize{
+        (y.rem_euclid(self.rows) * self.cols + x.rem_euclid(self.cols)) as usize
+    }
+
+    fn overlaps(&self, a: Point2, a_radius: f32, b: Point2, b_radius: f32) -> bool{
+        let reach = a_radius + b_radius;
+        wrapped_delta(a.x - b.x, self.bounds.w()).abs() <= reach
+            && wrapped_delta(a.y - b.y, self.bounds.h()).abs() <= reach
+    }
+
+    /* Visits every entry whose box overlaps the given one */
+    fn for_each_near<F: FnMut(&Entry)>(&self, position: Point2, radius: f32, mut f: F){
+        let reach = ((radius + self.max_radius) / self.cell_size).ceil() as i32;
+        let (cx, cy) = self.cell(position);
+
+        /* Don't visit a cell twice when the search wraps the whole field */
+        let x_span = (2 * reach + 1).min(self.cols);
+        let y_span = (2 * reach + 1).min(self.rows);
+        for y in (cy - reach)..(cy - reach + y_span){
+            for x in (cx - reach)..(cx - reach + x_span){
+                for entry in &self.cells[self.index(x, y)]{
+                    if self.overlaps(position, radius, entry.position, entry.radius){
+                        f(entry);
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn insert(&mut self, id: usize, position: Point2, radius: f32){
+        let (x, y) = self.cell(position);
+        let index = self.index(x, y);
+        self.cells[index].push(Entry{ id, position, radius });
+        self.max_radius = self.max_radius.max(radius);
+    }
+
+    /* Ids of everything whose box overlaps the given one */
+    pub fn query(&self, position: Point2, radius: f32, out: &mut Vec<usize>){
+        out.clear();
+        self.for_each_near(position, radius, |entry| out.push(entry.id));
+    }
+
+    /* Every pair of ids with overlapping boxes, each pair once with a < b */
+    pub fn pairs(&self, out: &mut Vec<(usize, usize)>){
+        out.clear();
+        for cell in &self.cells{
+            for a in cell{
+                self.for_each_near(a.position, a.radius, |b| {
+                    if a.id < b.id{
+                        out.push((a.id, b.id));
+                    }
+                });
+            }
+        }
+    }
+}
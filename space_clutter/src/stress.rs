@@ -0,0 +1,157 @@
+use nannou::prelude::*;
+use std::hint::black_box;
+use std::time::Instant;
+
+use crate::rng::Rng;
+use crate::spatial::{wrapped_offset, SpatialHash};
+use crate::weapon::{self, Arsenal};
+use crate::{build_broadphase, generate_asteroid, has_missile_hit_asteroid, missile_hits, Asteroid, Projectile};
+use crate::{ASTEROID_MAX_SIZE, ASTEROID_MIN_SIZE, ASTEROID_SPEED, BROADPHASE_CELL_SIZE, WINDOW_SIZE};
+
+const BENCH_COUNTS: [usize; 6] = [250, 500, 1000, 2000, 4000, 8000];
+const BENCH_MISSILES: usize = 64;
+const BENCH_ITERATIONS: u32 = 20;
+/* Rocks per window-sized area, the field grows with the count to keep this fixed */
+const BENCH_DENSITY: usize = 20;
+const BENCH_SEED: u64 = 1;
+
+/* `--stress N` overrides the asteroid cap so the field fills up with N rocks */
+pub fn max_asteroids() -> Option<u32>{
+    let args: Vec<String> = std::env::args().collect();
+    let position = args.iter().position(|arg| arg == "--stress")?;
+    args.get(position + 1)?.parse().ok()
+}
+
+fn playfield(count: usize) -> Rect{
+    let scale = ((count / BENCH_DENSITY).max(1) as f32).sqrt();
+    Rect::from_w_h(WINDOW_SIZE.0 as f32 * scale, WINDOW_SIZE.1 as f32 * scale)
+}
+
+fn random_point(rng: &mut Rng, win: Rect) -> Point2{
+    pt2(rng.range(win.left(), win.right()), rng.range(win.bottom(), win.top()))
+}
+
+/* `count` rocks and a spread of blaster shots over a field sized for them */
+fn scatter(rng: &mut Rng, count: usize) -> (Rect, Vec<Asteroid>, Vec<Projectile>){
+    let win = playfield(count);
+    let asteroids = (0..count)
+        .map(|_| {
+            let position = random_point(rng, win);
+            generate_asteroid(rng, position, 8, ASTEROID_MIN_SIZE, ASTEROID_MAX_SIZE, ASTEROID_SPEED, false)
+        })
+        .collect();
+    let blaster = &Arsenal::load().weapons[0];
+    let missiles = (0..BENCH_MISSILES)
+        .map(|_| {
+            let position = random_point(rng, win);
+            weapon::shot(0, blaster, position, 0.0)
+        })
+        .collect();
+    (win, asteroids, missiles)
+}
+
+fn naive_missile_hits(missiles: &mut [Projectile], asteroids: &[Asteroid], win: Rect) -> Vec<u32>{
+    let mut damage = vec![0; asteroids.len()];
+    for missile in missiles{
+        for (i, asteroid) in asteroids.iter().enumerate(){
+            if has_missile_hit_asteroid(missile, asteroid, win){
+                missile.hit = true;
+                damage[i] += missile.damage;
+            }
+        }
+    }
+    damage
+}
+
+/* Measured the short way round, so rocks either side of an edge can touch */
+fn overlapping(a: &Asteroid, b: &Asteroid, win: Rect) -> bool{
+    wrapped_offset(b.position - a.position, win).length() < (a.size + b.size) / 2.0
+}
+
+fn naive_pairs(asteroids: &[Asteroid], win: Rect) -> usize{
+    let mut count = 0;
+    for (i, a) in asteroids.iter().enumerate(){
+        for b in &asteroids[i + 1..]{
+            if overlapping(a, b, win){
+                count += 1;
+            }
+        }
+    }
+    count
+}
+
+fn grid_pairs(asteroids: &[Asteroid], grid: &SpatialHash, win: Rect, pairs: &mut Vec<(usize, usize)>) -> usize{
+    grid.pairs(pairs);
+    pairs.iter()
+        .filter(|&&(a, b)| overlapping(&asteroids[a], &asteroids[b], win))
+        .count()
+}
+
+fn time_ms<T, F: FnMut() -> T>(mut f: F) -> f64{
+    let start = Instant::now();
+    for _ in 0..BENCH_ITERATIONS{
+        black_box(f());
+    }
+    start.elapsed().as_secs_f64() * 1000.0 / BENCH_ITERATIONS as f64
+}
+
+/* Headless comparison of the nested loops against the spatial hash, run with `--bench` */
+pub fn run_benchmark(){
+    let mut pairs = Vec::new();
+    let mut rng = Rng::new(BENCH_SEED);
+
+    println!("{:>8} {:>14} {:>14} {:>14} {:>14}", "rocks", "naive hit ms", "grid hit ms", "naive pair ms", "grid pair ms");
+    for &count in &BENCH_COUNTS{
+        let (win, asteroids, mut missiles) = scatter(&mut rng, count);
+        let mut grid = SpatialHash::new(win, BROADPHASE_CELL_SIZE);
+
+        let naive_hit = time_ms(|| naive_missile_hits(&mut missiles, &asteroids, win));
+        let grid_hit = time_ms(|| {
+            build_broadphase(&mut grid, &asteroids);
+            missile_hits(&mut missiles, &asteroids, &grid, win)
+        });
+
+        let naive_pair = time_ms(|| naive_pairs(&asteroids, win));
+        let grid_pair = time_ms(|| {
+            build_broadphase(&mut grid, &asteroids);
+            grid_pairs(&asteroids, &grid, win, &mut pairs)
+        });
+
+        println!("{:>8} {:>14.3} {:>14.3} {:>14.3} {:>14.3}", count, naive_hit, grid_hit, naive_pair, grid_pair);
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    /* A rock either side of the left and right edges, touching only through the wrap,
+     * and a shot on the far side of the top edge from a third */
+    fn straddle(rng: &mut Rng, win: Rect, asteroids: &mut Vec<Asteroid>, missiles: &mut Vec<Projectile>){
+        for x in [win.left() + 2.0, win.right() - 2.0]{
+            asteroids.push(generate_asteroid(rng, pt2(x, win.y()), 8, ASTEROID_MIN_SIZE, ASTEROID_MIN_SIZE, ASTEROID_SPEED, false));
+        }
+        asteroids.push(generate_asteroid(rng, pt2(win.x(), win.top() - 2.0), 8, ASTEROID_MIN_SIZE, ASTEROID_MIN_SIZE, ASTEROID_SPEED, false));
+        missiles.push(weapon::shot(0, &Arsenal::load().weapons[0], pt2(win.x(), win.bottom() + 2.0), 0.0));
+    }
+
+    /* The grid has to find exactly what the nested loops find, seam and all */
+    #[test]
+    fn grid_matches_nested_loops(){
+        let mut rng = Rng::new(BENCH_SEED);
+        let mut pairs = Vec::new();
+        for count in [50, 250, 1000]{
+            let (win, mut asteroids, mut missiles) = scatter(&mut rng, count);
+            straddle(&mut rng, win, &mut asteroids, &mut missiles);
+            let mut grid = SpatialHash::new(win, BROADPHASE_CELL_SIZE);
+            build_broadphase(&mut grid, &asteroids);
+
+            let naive = naive_missile_hits(&mut missiles, &asteroids, win);
+            assert!(naive[count + 2] > 0, "the shot over the top edge missed");
+            assert_eq!(naive, missile_hits(&mut missiles, &asteroids, &grid, win));
+
+            assert!(overlapping(&asteroids[count], &asteroids[count + 1], win));
+            assert_eq!(naive_pairs(&asteroids, win), grid_pairs(&asteroids, &grid, win, &mut pairs));
+        }
+    }
+}
@@ -0,0 +1,110 @@
+use nannou::prelude::*;
+use std::time::Duration;
+
+use crate::hazard;
+use crate::rng::Rng;
+use crate::{generate_asteroid, Player, World};
+use crate::{ASTEROID_MAX_SIZE, ASTEROID_MIN_SIZE, SPACESHIP_HEIGHT};
+
+const WAVE_START_ASTEROIDS: u32 = 4;
+const WAVE_ASTEROID_INC: u32 = 2;
+const WAVE_MAX_ASTEROIDS: u32 = 16;
+const WAVE_BONUS: u32 = 5;
+const WAVE_INTERMISSION: Duration = Duration::from_secs(3);
+/* Keep edge spawns well clear of the ships */
+const WAVE_SAFE_DISTANCE: f32 = SPACESHIP_HEIGHT * 4.0;
+
+#[derive(Copy,Clone,PartialEq)]
+pub enum GameMode{
+    Endless, // Asteroids trickle in up to the difficulty cap
+    Waves,
+}
+
+impl GameMode{
+    pub fn name(self) -> &'static str{
+        match self{
+            GameMode::Endless => "ENDLESS",
+            GameMode::Waves => "WAVES",
+        }
+    }
+
+    pub fn toggle(self) -> GameMode{
+        match self{
+            GameMode::Endless => GameMode::Waves,
+            GameMode::Waves => GameMode::Endless,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Wave{
+    pub number: u32,
+    pub bonus: u32,
+    intermission: Duration,
+}
+
+impl Wave{
+    pub fn new() -> Wave{
+        Wave{
+            number: 0,
+            bonus: 0,
+            intermission: WAVE_INTERMISSION,
+        }
+    }
+
+    pub fn in_intermission(&self) -> bool{
+        self.intermission > Duration::ZERO
+    }
+
+    fn size(&self) -> u32{
+        (WAVE_START_ASTEROIDS + (self.number - 1) * WAVE_ASTEROID_INC).min(WAVE_MAX_ASTEROIDS)
+    }
+}
+
+impl Default for Wave{
+    fn default() -> Wave{
+        Wave::new()
+    }
+}
+
+fn edge_point(rng: &mut Rng, players: &[Player], win: Rect) -> Point2{
+    loop{
+        let along = rng.range(0.0, 1.0);
+        let point = match rng.below(4){
+            0 => pt2(win.left(), win.bottom() + along * win.h()),
+            1 => pt2(win.right(), win.bottom() + along * win.h()),
+            2 => pt2(win.left() + along * win.w(), win.bottom()),
+            _ => pt2(win.left() + along * win.w(), win.top()),
+        };
+
+        if players.iter().all(|player| point.distance(player.position) > WAVE_SAFE_DISTANCE){
+            return point;
+        }
+    }
+}
+
+/* Counts down the intermission, then spawns the next wave once the field is clear */
+pub fn wave_update(world: &mut World, dt: Duration, win: Rect){
+    if world.wave.in_intermission(){
+        world.wave.intermission = world.wave.intermission.saturating_sub(dt);
+        if !world.wave.in_intermission(){
+            world.wave.number += 1;
+            log::info!("Wave {}", world.wave.number);
+            hazard::place_wells(world, world.wave.number);
+            for _ in 0..world.crowd(world.wave.size()){
+                let position = edge_point(&mut world.rng, &world.players, win);
+                let asteroid = generate_asteroid(&mut world.rng, position, 8, ASTEROID_MIN_SIZE, ASTEROID_MAX_SIZE, world.difficulty.asteroid_speed, false);
+                world.asteroid.push(asteroid);
+            }
+        }
+    }
+    else if world.asteroid.is_empty(){
+        world.wave.bonus = WAVE_BONUS * world.wave.number;
+        /* Everyone still in the game gets the clear bonus */
+        for player in world.players.iter_mut().filter(|player| player.alive || player.lives > 0){
+            player.score += world.wave.bonus;
+        }
+        world.wave.intermission = WAVE_INTERMISSION;
+        world.wells.clear();
+    }
+}
@@ -0,0 +1,221 @@
+use nannou::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+use crate::config;
+use crate::ship::{angle_between, heading, rotation_of};
+use crate::spatial::wrapped_offset;
+use crate::{Asteroid, Player, Projectile, TIMESTEP};
+
+const WEAPONS_PATH: &str = "assets/weapons.toml";
+const DEFAULT_WEAPONS: &str = include_str!("../assets/weapons.toml");
+/* Longest a shot may fly or a reload take in seconds, anything past it is a typo */
+const MAX_SECONDS: f32 = 60.0;
+
+#[derive(Deserialize,Serialize,Copy,Clone,PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Behaviour{
+    Straight,
+    Pierce,
+    Homing,
+    Mine,
+}
+
+#[derive(Deserialize,Serialize,Copy,Clone,PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Shape{
+    Square,
+    Streak,
+    Dart,
+    Ring,
+}
+
+/* One row of the weapon table, everything about a shot from its flight to its sound */
+#[derive(Deserialize,Serialize,Clone)]
+pub struct Weapon{
+    pub name: String,
+    pub behaviour: Behaviour,
+    pub shape: Shape,
+    pub speed: f32,
+    pub lifetime: f32,
+    pub damage: u32,
+    pub cooldown: f32,
+    pub size: f32,
+    pub turn_rate: f32,
+    pub sound: String,
+    pub unlock_score: u32,
+}
+
+#[derive(Deserialize,Serialize,Clone)]
+pub struct Arsenal{
+    #[serde(rename = "weapon")]
+    pub weapons: Vec<Weapon>,
+}
+
+/* What's wrong with a weapon that would stop it working in play, if anything */
+fn problem(weapon: &Weapon) -> Option<String>{
+    let numbers = [("speed", weapon.speed), ("lifetime", weapon.lifetime), ("cooldown", weapon.cooldown), ("size", weapon.size), ("turn_rate", weapon.turn_rate)];
+    if let Some((name, value)) = numbers.into_iter().find(|(_, value)| !value.is_finite() || *value < 0.0){
+        return Some(format!("{} = {}", name, value));
+    }
+    let times = [("lifetime", weapon.lifetime), ("cooldown", weapon.cooldown)];
+    if let Some((name, value)) = times.into_iter().find(|(_, value)| *value > MAX_SECONDS){
+        return Some(format!("{} = {}, over {} seconds", name, value, MAX_SECONDS));
+    }
+    if weapon.damage == 0{
+        return Some("damage = 0".to_string());
+    }
+    None
+}
+
+impl Arsenal{
+    /* One bad weapon throws out the whole file, the rest may well depend on its place in the order */
+    pub fn load() -> Arsenal{
+        let arsenal: Arsenal = config::load(WEAPONS_PATH, DEFAULT_WEAPONS);
+        if arsenal.weapons.is_empty(){
+            log::warn!("{} has no weapons, using defaults", WEAPONS_PATH);
+            return toml::from_str(DEFAULT_WEAPONS).expect("Built-in config is invalid");
+        }
+        for weapon in &arsenal.weapons{
+            if let Some(problem) = problem(weapon){
+                log::warn!("{} weapon {} has {}, using defaults", WEAPONS_PATH, weapon.name, problem);
+                return toml::from_str(DEFAULT_WEAPONS).expect("Built-in config is invalid");
+            }
+        }
+        arsenal
+    }
+}
+
+/* A fresh shot from `weapon`, the projectile keeps its own copy of what the hit tests need */
+pub fn shot(index: usize, weapon: &Weapon, position: Point2, rotation: f32) -> Projectile{
+    Projectile{
+        hit: false,
+        position,
+        rotation,
+        previous_position: position,
+        weapon: index,
+        behaviour: weapon.behaviour,
+        velocity: heading(rotation) * weapon.speed,
+        size: weapon.size,
+        damage: weapon.damage,
+        life: Duration::from_secs_f32(weapon.lifetime),
+    }
+}
+
+/* Next weapon along that the ship's score has unlocked, round to the first again after the last */
+pub fn switch(player: &mut Player, arsenal: &Arsenal){
+    let count = arsenal.weapons.len();
+    player.weapon = (1..=count)
+        .map(|step| (player.weapon + step) % count)
+        .find(|&index| arsenal.weapons[index].unlock_score <= player.score)
+        .unwrap_or(0);
+}
+
+/* Moves a shot on one step, homing ones turn towards the closest rock first.
+ * Shots that wrap across `field` look for it the short way round the seam. */
+pub fn fly(missile: &mut Projectile, weapon: &Weapon, asteroids: &[Asteroid], field: Option<Rect>){
+    if missile.velocity != Vec2::ZERO{
+        missile.rotation = rotation_of(missile.velocity);
+    }
+
+    if missile.behaviour == Behaviour::Homing{
+        let nearest = asteroids.iter()
+            .map(|asteroid| asteroid.position - missile.position)
+            .map(|offset| field.map_or(offset, |field| wrapped_offset(offset, field)))
+            .min_by(|a, b| a.length_squared().total_cmp(&b.length_squared()));
+        if let Some(offset) = nearest{
+            let most = deg_to_rad(weapon.turn_rate) * TIMESTEP.as_secs_f32();
+            missile.rotation += angle_between(missile.rotation, rotation_of(offset)).clamp(-most, most);
+            missile.velocity = heading(missile.rotation) * missile.velocity.length();
+        }
+    }
+
+    missile.position += missile.velocity;
+    missile.life = missile.life.saturating_sub(TIMESTEP);
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use crate::rng::Rng;
+    use crate::{generate_asteroid, new_player, playfield};
+
+    fn defaults() -> Arsenal{
+        toml::from_str(DEFAULT_WEAPONS).unwrap()
+    }
+
+    fn named(arsenal: &Arsenal, name: &str) -> usize{
+        arsenal.weapons.iter().position(|weapon| weapon.name == name).unwrap()
+    }
+
+    #[test]
+    fn switching_skips_locked_weapons_and_wraps(){
+        let arsenal = defaults();
+        let mut player = new_player(pt2(0.0, 0.0), 1);
+        switch(&mut player, &arsenal);
+        assert_eq!(player.weapon, 0, "nothing else is unlocked at no score");
+
+        player.score = arsenal.weapons[1].unlock_score;
+        switch(&mut player, &arsenal);
+        assert_eq!(player.weapon, 1);
+        switch(&mut player, &arsenal);
+        assert_eq!(player.weapon, 0);
+
+        player.score = u32::MAX;
+        for expected in (1..arsenal.weapons.len()).chain([0]){
+            switch(&mut player, &arsenal);
+            assert_eq!(player.weapon, expected);
+        }
+    }
+
+    #[test]
+    fn shots_fly_straight_and_run_out(){
+        let arsenal = defaults();
+        let blaster = &arsenal.weapons[0];
+        let mut missile = shot(0, blaster, pt2(0.0, 0.0), 0.0);
+        let steps = (blaster.lifetime / TIMESTEP.as_secs_f32()).ceil() as u32;
+        for _ in 0..steps{
+            assert!(!missile.life.is_zero());
+            fly(&mut missile, blaster, &[], None);
+        }
+        assert!(missile.life.is_zero());
+        assert!(missile.position.x.abs() < 1e-3);
+        assert!((missile.position.y - blaster.speed * steps as f32).abs() < 1e-2);
+    }
+
+    #[test]
+    fn homing_shots_turn_no_faster_than_their_rate(){
+        let arsenal = defaults();
+        let index = named(&arsenal, "HOMING");
+        let homing = &arsenal.weapons[index];
+        let rock = generate_asteroid(&mut Rng::new(1), pt2(100.0, 0.0), 8, 40.0, 40.0, 0.0, false);
+        let mut missile = shot(index, homing, pt2(0.0, 0.0), 0.0);
+
+        fly(&mut missile, homing, std::slice::from_ref(&rock), None);
+        let most = deg_to_rad(homing.turn_rate) * TIMESTEP.as_secs_f32();
+        assert!((missile.rotation + most).abs() < 1e-4, "turned {} towards the rock on the right", missile.rotation);
+        assert!((missile.velocity.length() - homing.speed).abs() < 1e-4);
+
+        /* Across the seam the rock is closer the other way */
+        let field = playfield();
+        let rock = generate_asteroid(&mut Rng::new(1), pt2(field.right() - 10.0, 0.0), 8, 40.0, 40.0, 0.0, false);
+        let mut missile = shot(index, homing, pt2(field.left() + 10.0, 0.0), 0.0);
+        fly(&mut missile, homing, std::slice::from_ref(&rock), Some(field));
+        assert!(missile.rotation > 0.0, "turned away from the seam");
+    }
+
+    #[test]
+    fn out_of_range_numbers_are_problems(){
+        let weapon = defaults().weapons[0].clone();
+        assert!(problem(&weapon).is_none());
+        for broken in [
+            Weapon{ lifetime: -1.0, ..weapon.clone() },
+            Weapon{ lifetime: MAX_SECONDS + 1.0, ..weapon.clone() },
+            Weapon{ cooldown: f32::INFINITY, ..weapon.clone() },
+            Weapon{ speed: f32::NAN, ..weapon.clone() },
+            Weapon{ damage: 0, ..weapon.clone() },
+        ]{
+            assert!(problem(&broken).is_some());
+        }
+    }
+}